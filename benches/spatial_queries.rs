@@ -0,0 +1,90 @@
+//! Compares proximity queries through the simulation's spatial index with
+//! the linear scans they replaced, then times whole ticks at scale.
+//!
+//! Run with `cargo bench --bench spatial_queries`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use rust_rts::entity::UnitType;
+use rust_rts::game::Simulation;
+use rust_rts::game::definitions::{Definitions, DEFINITIONS_PATH};
+use rust_rts::game::simulation::TICK_DT;
+
+const UNIT_COUNTS: [usize; 3] = [500, 2000, 5000];
+const SEARCH_RANGE: f32 = 150.0;
+const TICKS: u32 = 30;
+
+// A match with `count` fighters scattered across the map, split between two players
+fn populated_simulation(count: usize) -> Simulation {
+    let definitions = Definitions::load(DEFINITIONS_PATH).expect("failed to load definitions");
+    let mut simulation = Simulation::new(42, definitions);
+
+    for i in 0..count {
+        let x = simulation.rng.gen_range(0.0, simulation.map_width);
+        let y = simulation.rng.gen_range(0.0, simulation.map_height);
+        simulation.spawn_unit(UnitType::Fighter, x, y, i % 2);
+    }
+
+    simulation.rebuild_spatial_index();
+    simulation
+}
+
+// The scan every proximity query used before the spatial index
+fn linear_nearest_enemy(simulation: &Simulation, x: f32, y: f32, player_id: usize, max_range: f32) -> Option<(f32, f32)> {
+    let mut nearest_distance = max_range;
+    let mut nearest_pos = None;
+
+    for unit in &simulation.units {
+        if unit.player_id != player_id && unit.health > 0.0 {
+            let distance = ((unit.x - x).powi(2) + (unit.y - y).powi(2)).sqrt();
+            if distance < nearest_distance {
+                nearest_distance = distance;
+                nearest_pos = Some((unit.x, unit.y));
+            }
+        }
+    }
+
+    nearest_pos
+}
+
+fn time<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let start = Instant::now();
+    let result = f();
+    (start.elapsed(), result)
+}
+
+fn main() {
+    println!("{:>6}  {:>14}  {:>14}  {:>8}  {:>12}", "units", "linear scan", "spatial index", "speedup", "tick");
+
+    for count in UNIT_COUNTS {
+        let mut simulation = populated_simulation(count);
+
+        // Every unit looks for its nearest enemy, as auto-targeting does each tick
+        let (linear_time, linear_results) = time(|| {
+            simulation.units.iter()
+                .map(|u| black_box(linear_nearest_enemy(&simulation, u.x, u.y, u.player_id, SEARCH_RANGE)))
+                .collect::<Vec<_>>()
+        });
+        let (indexed_time, indexed_results) = time(|| {
+            simulation.units.iter()
+                .map(|u| black_box(simulation.find_nearest_enemy(u.x, u.y, u.player_id, SEARCH_RANGE)))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(linear_results, indexed_results, "spatial index disagrees with the linear scan");
+
+        let (tick_time, _) = time(|| {
+            for _ in 0..TICKS {
+                simulation.tick(TICK_DT, &[]);
+            }
+        });
+
+        println!(
+            "{:>6}  {:>12.2}ms  {:>12.2}ms  {:>7.1}x  {:>10.2}ms",
+            count,
+            linear_time.as_secs_f64() * 1000.0,
+            indexed_time.as_secs_f64() * 1000.0,
+            linear_time.as_secs_f64() / indexed_time.as_secs_f64(),
+            tick_time.as_secs_f64() * 1000.0 / TICKS as f64,
+        );
+    }
+}
@@ -1,186 +1,174 @@
-use crate::game::state::GameState;
+use crate::game::commands::{Command, PlayerCommand};
+use crate::game::rng::SimRng;
+use crate::game::view::PlayerView;
 use crate::entity::{BuildingType, UnitType};
-use macroquad::rand::gen_range;
+use crate::game::types::ResourceType;
 
-pub fn manage_resources(game_state: &mut GameState) {
-    let ai_player_id = 1;
+// Spots around headquarters tried per building before giving up until next time
+const PLACEMENT_ATTEMPTS: usize = 12;
+
+// The AI only knows what its own units can see, so everything it reads
+// about the world goes through the player's view
+
+pub fn manage_resources(view: &PlayerView) -> Vec<PlayerCommand> {
+    let ai_player_id = view.player_id;
+    let node_type = |node_idx: Option<usize>| {
+        node_idx.and_then(|node_idx| view.resource_node(node_idx))
+            .filter(|node| node.resources > 0)
+            .map(|node| node.resource_type.clone())
+    };
     
-    // Find all AI workers
-    let mut worker_positions = Vec::new();
-    for unit in &game_state.units {
-        if unit.player_id == ai_player_id && unit.unit_type == UnitType::Worker {
-            let carrying = unit.current_resources.unwrap_or(0);
-            worker_positions.push((unit.id, unit.x, unit.y, carrying));
-        }
-    }
+    // Workers that aren't off building something, with the kind of node they mine
+    let workers: Vec<_> = view.own_units()
+        .filter(|unit| unit.unit_type == UnitType::Worker)
+        .filter(|unit| unit.orders.iter().all(|order| matches!(order, Command::Gather { .. })))
+        .map(|unit| (unit.id, unit.x, unit.y, unit.current_resources.unwrap_or(0), node_type(unit.gather_node)))
+        .collect();
     
-    // Assign workers to nearby resource nodes if they're not carrying resources
-    for (worker_id, worker_x, worker_y, carrying) in worker_positions {
-        if carrying == 0 {
-            // Find nearest resource node
-            let mut nearest_distance = f32::MAX;
-            let mut nearest_node_pos = None;
-            
-            for node in &game_state.resource_nodes {
-                if node.resources > 0 {
-                    let distance = ((node.x - worker_x).powi(2) + (node.y - worker_y).powi(2)).sqrt();
-                    if distance < nearest_distance {
-                        nearest_distance = distance;
-                        nearest_node_pos = Some((node.x, node.y));
-                    }
-                }
+    // Keep about one worker in four on energy, moving empty-handed workers
+    // over when it's out of balance and sending idle ones where they're needed
+    let wanted_energy_workers = workers.len().div_ceil(4);
+    let mut energy_workers = workers.iter().filter(|worker| worker.4 == Some(ResourceType::Energy)).count();
+    let mut assignments = Vec::new();
+    for (worker_id, worker_x, worker_y, carrying, mining) in workers {
+        let wanted = match (mining.clone(), carrying) {
+            (None, _) => if energy_workers < wanted_energy_workers { ResourceType::Energy } else { ResourceType::Minerals },
+            (Some(ResourceType::Energy), 0) if energy_workers > wanted_energy_workers => ResourceType::Minerals,
+            (Some(ResourceType::Minerals), 0) if energy_workers < wanted_energy_workers => ResourceType::Energy,
+            _ => continue,
+        };
+        let node = view.find_nearest_resource(worker_x, worker_y, Some(&wanted))
+            .or_else(|| view.find_nearest_resource(worker_x, worker_y, None));
+        if let Some(resource_id) = node {
+            if mining == Some(ResourceType::Energy) {
+                energy_workers -= 1;
             }
-            
-            // Assign worker to gather from nearest node
-            if let Some((node_x, node_y)) = nearest_node_pos {
-                for unit in &mut game_state.units {
-                    if unit.id == worker_id {
-                        unit.target_x = Some(node_x);
-                        unit.target_y = Some(node_y);
-                        break;
-                    }
-                }
+            if node_type(Some(resource_id)) == Some(ResourceType::Energy) {
+                energy_workers += 1;
             }
+            assignments.push((worker_id, resource_id));
         }
     }
+    
+    assignments.into_iter()
+        .map(|(worker_id, resource_id)| PlayerCommand::new(ai_player_id, vec![worker_id], Command::Gather { resource_id }))
+        .collect()
 }
 
-pub fn build_structure(game_state: &mut GameState, building_type: BuildingType) {
-    let ai_player_id = 1;
+pub fn build_structure(view: &PlayerView, rng: &mut SimRng, building_type: BuildingType) -> Option<PlayerCommand> {
+    let ai_player_id = view.player_id;
     
     // Find AI headquarters for positioning
-    let mut hq_pos = None;
-    for unit in &game_state.units {
-        if unit.player_id == ai_player_id && unit.unit_type == UnitType::Headquarters {
-            hq_pos = Some((unit.x, unit.y));
-            break;
-        }
+    let (hq_x, hq_y) = view.find_headquarters()?;
+
+    let cost = view.definitions().building(&building_type).cost;
+    let player = view.player()?;
+    let unlocked = view.has_prerequisites(&view.definitions().building(&building_type).prerequisites);
+    if !unlocked || player.minerals < cost.minerals || player.energy < cost.energy {
+        return None;
     }
-    
-    if let Some((hq_x, hq_y)) = hq_pos {
-        let cost = get_building_cost(&building_type);
-        
-        if game_state.players[ai_player_id].minerals >= cost {
-            // Place building near headquarters
-            let build_x = hq_x + gen_range(80.0, 150.0);
-            let build_y = hq_y + gen_range(-100.0, 100.0);
-            
-            let building_id = game_state.spawn_unit(UnitType::Building, build_x, build_y, ai_player_id);
-            
-            // Set building type
-            for unit in &mut game_state.units {
-                if unit.id == building_id {
-                    unit.building_type = Some(building_type);
-                    unit.construction_progress = Some(0.0);
-                    break;
-                }
-            }
-            
-            // Deduct cost
-            game_state.players[ai_player_id].minerals -= cost;
+
+    // Place building near headquarters, looking further out as the base
+    // fills up; try again next time rather than building in a lake or
+    // on top of something. Only what we can see is checked, the
+    // simulation turns down the rest
+    let mut spot = None;
+    for attempt in 0..PLACEMENT_ATTEMPTS {
+        let distance = 90.0 + attempt as f32 * 20.0;
+        let angle = rng.gen_range(0.0, std::f32::consts::TAU);
+        let (x, y) = (hq_x + angle.cos() * distance, hq_y + angle.sin() * distance);
+        if view.can_place_building(&building_type, x, y) {
+            spot = Some((x, y));
+            break;
         }
     }
-}
+    let (build_x, build_y) = spot?;
 
-fn get_building_cost(building_type: &BuildingType) -> i32 {
-    match building_type {
-        BuildingType::Barracks => 150,
-        BuildingType::ResourceDepot => 175,
-        BuildingType::DefenseTurret => 100,
-        _ => 200,
-    }
+    // Send the closest worker that's idle or gathering rather than building;
+    // it pays once it gets there
+    let builder_id = view.own_units()
+        .filter(|unit| unit.unit_type == UnitType::Worker)
+        .filter(|unit| unit.orders.iter().all(|order| matches!(order, Command::Gather { .. })))
+        .min_by(|a, b| {
+            let distance_a = (a.x - build_x).powi(2) + (a.y - build_y).powi(2);
+            let distance_b = (b.x - build_x).powi(2) + (b.y - build_y).powi(2);
+            distance_a.total_cmp(&distance_b)
+        })
+        .map(|unit| unit.id)?;
+
+    Some(PlayerCommand::new(
+        ai_player_id,
+        vec![builder_id],
+        Command::Build { building_type, x: build_x, y: build_y },
+    ))
 }
 
-pub fn plan_attack(game_state: &mut GameState) {
-    let ai_player_id = 1;
-    
+pub fn plan_attack(view: &PlayerView) -> Option<PlayerCommand> {
+    let ai_player_id = view.player_id;
+
     // Count combat units
-    let mut combat_units = Vec::new();
-    for unit in &game_state.units {
-        if unit.player_id == ai_player_id && 
-           matches!(unit.unit_type, UnitType::Fighter | UnitType::Ranger | UnitType::Tank) {
-            combat_units.push(unit.id);
-        }
-    }
+    let combat_units: Vec<u32> = view.own_units()
+        .filter(|unit| matches!(unit.unit_type, UnitType::Fighter | UnitType::Ranger | UnitType::Tank))
+        .map(|unit| unit.id)
+        .collect();
     
-    // If we have enough units, launch an attack on enemy base
-    if combat_units.len() >= 3 {
-        // Find enemy headquarters
-        let mut enemy_hq_pos = None;
-        for unit in &game_state.units {
-            if unit.player_id != ai_player_id && unit.unit_type == UnitType::Headquarters {
-                enemy_hq_pos = Some((unit.x, unit.y));
-                break;
-            }
-        }
-        
-        if let Some((hq_x, hq_y)) = enemy_hq_pos {
-            // Send all combat units to attack enemy HQ
-            for unit_id in combat_units {
-                for unit in &mut game_state.units {
-                    if unit.id == unit_id {
-                        unit.target_x = Some(hq_x + gen_range(-50.0, 50.0));
-                        unit.target_y = Some(hq_y + gen_range(-50.0, 50.0));
-                        break;
-                    }
-                }
-            }
-        }
+    // Wait until we have enough units to launch an attack on enemy base
+    if combat_units.len() < 3 {
+        return None;
     }
+
+    // Go for an enemy headquarters we've seen, then any other building,
+    // and otherwise scout the closest start location we haven't explored
+    let known = view.known_enemy_buildings();
+    let (hq_x, hq_y) = known.iter()
+        .find(|building| building.unit_type == UnitType::Headquarters)
+        .or_else(|| known.first())
+        .map(|building| (building.x, building.y))
+        .or_else(|| {
+            let (home_x, home_y) = view.find_headquarters()?;
+            view.start_locations().iter()
+                .filter(|&&(x, y)| !view.is_explored(x, y))
+                .min_by(|a, b| {
+                    let distance_a = (a.0 - home_x).powi(2) + (a.1 - home_y).powi(2);
+                    let distance_b = (b.0 - home_x).powi(2) + (b.1 - home_y).powi(2);
+                    distance_a.total_cmp(&distance_b)
+                })
+                .copied()
+        })?;
+
+    // Send all combat units to attack enemy HQ together, fighting whatever they meet
+    Some(PlayerCommand::new(ai_player_id, combat_units, Command::AttackMove { x: hq_x, y: hq_y }))
 }
 
-pub fn make_decisions(game_state: &mut GameState) {
-    let ai_player_id = 1;
+pub fn make_decisions(view: &PlayerView) -> Vec<PlayerCommand> {
+    let ai_player_id = view.player_id;
     
     // Count different unit types
     let mut _worker_count = 0;    // Fixed unused variable warning with underscore
     let mut _fighter_count = 0;   // Fixed unused variable warning with underscore
     let mut ai_units = Vec::new();
     
-    for unit in &game_state.units {
-        if unit.player_id == ai_player_id {
-            ai_units.push((unit.id, unit.x, unit.y, unit.unit_type.clone()));
-            match unit.unit_type {
-                UnitType::Worker => _worker_count += 1,
-                UnitType::Fighter => _fighter_count += 1,
-                _ => {}
-            }
+    for unit in view.own_units() {
+        ai_units.push((unit.id, unit.x, unit.y, unit.unit_type.clone(), unit.orders.is_empty()));
+        match unit.unit_type {
+            UnitType::Worker => _worker_count += 1,
+            UnitType::Fighter => _fighter_count += 1,
+            _ => {}
         }
     }
     
-    // Make combat units patrol and attack enemies
-    for (unit_id, unit_x, unit_y, unit_type) in ai_units {
-        if matches!(unit_type, UnitType::Fighter | UnitType::Ranger | UnitType::Tank) {
+    // Make combat units without orders attack enemies that come near; the
+    // rest are left to their orders and stance
+    let mut commands = Vec::new();
+    for (unit_id, unit_x, unit_y, unit_type, idle) in ai_units {
+        if idle && matches!(unit_type, UnitType::Fighter | UnitType::Ranger | UnitType::Tank) {
             // Look for nearby enemies
-            let mut nearest_enemy = None;
-            let mut nearest_distance = 200.0; // Attack range
-            
-            for enemy in &game_state.units {
-                if enemy.player_id != ai_player_id {
-                    let distance = ((enemy.x - unit_x).powi(2) + (enemy.y - unit_y).powi(2)).sqrt();
-                    if distance < nearest_distance {
-                        nearest_distance = distance;
-                        nearest_enemy = Some((enemy.x, enemy.y));
-                    }
-                }
-            }
+            let nearest_enemy = view.find_nearest_enemy(unit_x, unit_y, 200.0); // Attack range
             
-            // Attack nearest enemy or patrol
-            for unit in &mut game_state.units {
-                if unit.id == unit_id {
-                    if let Some((enemy_x, enemy_y)) = nearest_enemy {
-                        unit.target_x = Some(enemy_x);
-                        unit.target_y = Some(enemy_y);
-                    } else if unit.target_x.is_none() {
-                        // Patrol around base
-                        let patrol_x = unit_x + gen_range(-100.0, 100.0);
-                        let patrol_y = unit_y + gen_range(-100.0, 100.0);
-                        unit.target_x = Some(patrol_x);
-                        unit.target_y = Some(patrol_y);
-                    }
-                    break;
-                }
+            if let Some((enemy_x, enemy_y)) = nearest_enemy {
+                commands.push(PlayerCommand::new(ai_player_id, vec![unit_id], Command::AttackMove { x: enemy_x, y: enemy_y }));
             }
         }
     }
+    commands
 }
@@ -1,33 +1,70 @@
-use crate::game::state::GameState;
+use std::collections::{HashMap, HashSet};
+use crate::game::commands::{Command, PlayerCommand};
+use crate::game::definitions::UPGRADE_TYPES;
+use crate::game::rng::SimRng;
+use crate::game::view::PlayerView;
 use crate::entity::{UnitType, BuildingType};
 use super::behaviors;
 
+/// Structures the AI puts up, in order; each waits for its prerequisites.
+const BUILD_ORDER: [BuildingType; 3] = [BuildingType::Barracks, BuildingType::Factory, BuildingType::ResearchCenter];
+
+/// How many of each unit the AI keeps, in training priority order.
+const ARMY_TARGETS: [(UnitType, usize); 4] = [
+    (UnitType::Worker, 5),
+    (UnitType::Fighter, 5),
+    (UnitType::Ranger, 4),
+    (UnitType::Tank, 2),
+];
+
+/// Free supply the AI keeps; below it, it puts up another Supply Depot.
+const SUPPLY_MARGIN: u32 = 3;
+
+/// Minerals the AI keeps back for units when starting research.
+const RESEARCH_RESERVE: i32 = 100;
+
+/// Drives one computer-controlled player.
 pub struct AIController {
+    player_id: usize,
     think_timer: f32,
     resource_timer: f32,
     attack_timer: f32,
     build_timer: f32,
     last_unit_built: Option<UnitType>,
-    has_barracks: bool,
-    has_factory: bool,
+    /// Building types it owns or has a worker on the way to build.
+    buildings: HashSet<BuildingType>,
+    /// Whether a Supply Depot is going up or a worker is on the way to build one.
+    supply_pending: bool,
+    /// Its own randomness, so thinking never disturbs the simulation's.
+    rng: SimRng,
 }
 
 impl AIController {
-    pub fn new() -> Self {
+    pub fn new(player_id: usize, seed: u64) -> Self {
         Self {
+            player_id,
             think_timer: 0.0,
             resource_timer: 0.0,
             attack_timer: 0.0,
             build_timer: 10.0,  // Initial delay before building
             last_unit_built: None,
-            has_barracks: false,
-            has_factory: false,
+            buildings: HashSet::new(),
+            supply_pending: false,
+            rng: SimRng::new(seed),
         }
     }
+
+    pub fn player_id(&self) -> usize {
+        self.player_id
+    }
     
-    pub fn update(&mut self, game_state: &mut GameState) {
+    /// Think for one tick and return the orders to feed into the next
+    /// `Simulation::tick`, like a player's input. It only ever sees the
+    /// world through its player's view.
+    pub fn update(&mut self, view: &PlayerView, dt: f32) -> Vec<PlayerCommand> {
+        let mut commands = Vec::new();
+
         // Update AI timers
-        let dt = 0.016; // Assuming ~60 FPS
         self.think_timer += dt;
         self.resource_timer += dt;
         self.attack_timer += dt;
@@ -36,131 +73,195 @@ impl AIController {
         // Process resource gathering (more frequently)
         if self.resource_timer >= 0.5 {
             self.resource_timer = 0.0;
-            behaviors::manage_resources(game_state);
+            commands.extend(behaviors::manage_resources(view));
         }
         
         // Process general AI decision making
         if self.think_timer >= 1.0 {
             self.think_timer = 0.0;
-            behaviors::make_decisions(game_state);
+            commands.extend(behaviors::make_decisions(view));
         }
         
         // Decide on attacking
         if self.attack_timer >= 30.0 {
             self.attack_timer = 0.0;
-            behaviors::plan_attack(game_state);
+            commands.extend(behaviors::plan_attack(view));
         }
         
         // Check buildings
-        self.check_buildings(game_state);
+        self.check_buildings(view);
         
         // Handle building and training
         if self.build_timer >= 15.0 {
             self.build_timer = 0.0;
-            self.build_or_train(game_state);
+            commands.extend(self.build_or_train(view));
         }
+        commands
     }
     
-    fn check_buildings(&mut self, game_state: &GameState) {
-        self.has_barracks = false;
-        self.has_factory = false;
+    fn check_buildings(&mut self, view: &PlayerView) {
+        self.buildings.clear();
+        self.supply_pending = false;
         
-        for unit in &game_state.units {
-            if unit.player_id == 1 && unit.unit_type == UnitType::Building {
-                if let Some(building_type) = &unit.building_type {
-                    if *building_type == BuildingType::Barracks {
-                        self.has_barracks = true;
-                    }
-                    if *building_type == BuildingType::Factory {
-                        self.has_factory = true;
-                    }
-                }
+        // Buildings still going up, or a worker on the way to start one, count
+        // too so the AI doesn't order a second
+        for unit in view.own_units() {
+            if let Some(Command::Build { building_type, .. }) = unit.orders.front() {
+                self.buildings.insert(building_type.clone());
+                self.supply_pending |= *building_type == BuildingType::SupplyDepot;
+            }
+            if unit.building_type == Some(BuildingType::SupplyDepot) && !unit.is_complete() {
+                self.supply_pending = true;
+            }
+            if let Some(building_type) = &unit.building_type {
+                self.buildings.insert(building_type.clone());
             }
         }
     }
     
-    fn build_or_train(&mut self, game_state: &mut GameState) {
-        let ai_player = &game_state.players[1];
+    fn build_or_train(&mut self, view: &PlayerView) -> Vec<PlayerCommand> {
+        let player_id = self.player_id;
+        let mut commands = Vec::new();
+        let player = match view.player() {
+            Some(player) => player,
+            None => return commands,
+        };
         
-        // Train units if we have enough resources
-        if !self.has_barracks && ai_player.minerals >= 150 {
-            // Need to build a barracks
-            behaviors::build_structure(game_state, BuildingType::Barracks);
-        } else if !self.has_factory && ai_player.minerals >= 200 && self.has_barracks {
-            // Build a factory next
-            behaviors::build_structure(game_state, BuildingType::Factory);
-        } else {
-            // Train units
-            let ai_headquarters = game_state.units.iter().find(|u| 
-                u.player_id == 1 && u.unit_type == UnitType::Headquarters
-            );
-            
-            if let Some(headquarters) = ai_headquarters {
-                // Count current units
-                let mut worker_count = 0;
-                let mut fighter_count = 0;
-                let mut ranger_count = 0;
-                let mut tank_count = 0;
-                
-                for unit in &game_state.units {
-                    if unit.player_id == 1 {
-                        match unit.unit_type {
-                            UnitType::Worker => worker_count += 1,
-                            UnitType::Fighter => fighter_count += 1,
-                            UnitType::Ranger => ranger_count += 1,
-                            UnitType::Tank => tank_count += 1,
-                            _ => {}
-                        }
-                    }
+        // Stay ahead of the supply cap so training never stalls on it
+        let needs_supply = !self.supply_pending
+            && player.supply_cap < view.definitions().max_supply
+            && player.supply_used + SUPPLY_MARGIN >= player.supply_cap;
+        if needs_supply && can_build(view, &BuildingType::SupplyDepot) {
+            commands.extend(behaviors::build_structure(view, &mut self.rng, BuildingType::SupplyDepot));
+            return commands;
+        }
+        
+        // Next structure in the build order, once it's unlocked and affordable
+        let next_building = BUILD_ORDER.iter()
+            .find(|building_type| !self.buildings.contains(building_type))
+            .filter(|building_type| can_build(view, building_type));
+        
+        if let Some(building_type) = next_building {
+            commands.extend(behaviors::build_structure(view, &mut self.rng, building_type.clone()));
+        } else if view.find_headquarters().is_some() {
+            // Count current units, including the ones already being trained
+            let mut counts: HashMap<UnitType, usize> = HashMap::new();
+            for unit in view.own_units() {
+                for unit_type in std::iter::once(&unit.unit_type).chain(&unit.production_queue) {
+                    *counts.entry(unit_type.clone()).or_insert(0) += 1;
                 }
+            }
+            
+            // Decide what to train: the first type below its target that is
+            // unlocked and affordable
+            let unit_type = ARMY_TARGETS.iter()
+                .filter(|(unit_type, target)| counts.get(unit_type).copied().unwrap_or(0) < *target)
+                .map(|(unit_type, _)| unit_type)
+                .find(|unit_type| {
+                    let prerequisites = &view.definitions().unit(unit_type).prerequisites;
+                    view.has_prerequisites(prerequisites)
+                        && view.can_afford(unit_type)
+                        && view.has_supply(unit_type)
+                })
+                .cloned();
+            
+            // Queue it at any building that trains it, the same way a player would
+            if let Some(unit_type) = unit_type {
+                let producers: Vec<u32> = view.own_units()
+                    .filter(|unit| view.definitions().can_train(unit, &unit_type))
+                    .map(|unit| unit.id)
+                    .collect();
                 
-                // Decide what to train
-                let hq_x = headquarters.x;
-                let hq_y = headquarters.y;
-                
-                if worker_count < 5 && game_state.can_afford(1, &UnitType::Worker) {
-                    // Train worker
-                    let _id = game_state.spawn_unit(UnitType::Worker, hq_x + 30.0, hq_y, 1);
-                    game_state.deduct_cost(1, &UnitType::Worker);
-                    self.last_unit_built = Some(UnitType::Worker);
-                } else if self.has_barracks && fighter_count < 5 && game_state.can_afford(1, &UnitType::Fighter) {
-                    // Train fighter
-                    let barracks = game_state.units.iter().find(|u| 
-                        u.player_id == 1 && u.unit_type == UnitType::Building && 
-                        u.building_type == Some(BuildingType::Barracks)
-                    );
-                    
-                    if let Some(barracks) = barracks {
-                        let _id = game_state.spawn_unit(UnitType::Fighter, barracks.x + 30.0, barracks.y, 1);
-                        game_state.deduct_cost(1, &UnitType::Fighter);
-                        self.last_unit_built = Some(UnitType::Fighter);
-                    }
-                } else if self.has_barracks && ranger_count < 4 && game_state.can_afford(1, &UnitType::Ranger) {
-                    // Train ranger
-                    let barracks = game_state.units.iter().find(|u| 
-                        u.player_id == 1 && u.unit_type == UnitType::Building && 
-                        u.building_type == Some(BuildingType::Barracks)
-                    );
-                    
-                    if let Some(barracks) = barracks {
-                        let _id = game_state.spawn_unit(UnitType::Ranger, barracks.x + 30.0, barracks.y, 1);
-                        game_state.deduct_cost(1, &UnitType::Ranger);
-                        self.last_unit_built = Some(UnitType::Ranger);
-                    }
-                } else if self.has_factory && tank_count < 2 && game_state.can_afford(1, &UnitType::Tank) {
-                    // Train tank
-                    let factory = game_state.units.iter().find(|u| 
-                        u.player_id == 1 && u.unit_type == UnitType::Building && 
-                        u.building_type == Some(BuildingType::Factory)
-                    );
-                    
-                    if let Some(factory) = factory {
-                        let _id = game_state.spawn_unit(UnitType::Tank, factory.x + 40.0, factory.y, 1);
-                        game_state.deduct_cost(1, &UnitType::Tank);
-                        self.last_unit_built = Some(UnitType::Tank);
-                    }
+                if !producers.is_empty() {
+                    commands.push(PlayerCommand::new(player_id, producers, Command::Train { unit_type: unit_type.clone() }));
+                    self.last_unit_built = Some(unit_type);
                 }
             }
         }
+        
+        commands.extend(self.research(view));
+        commands
+    }
+    
+    /// Start an upgrade at an idle Research Center when there are resources
+    /// to spare after paying for it.
+    fn research(&mut self, view: &PlayerView) -> Option<PlayerCommand> {
+        let player_id = self.player_id;
+        let center = view.own_units()
+            .find(|unit| {
+                unit.building_type == Some(BuildingType::ResearchCenter) && unit.is_complete() && unit.research.is_none()
+            })
+            .map(|unit| unit.id);
+        let center = center?;
+        
+        let player = view.player()?;
+        let upgrade = UPGRADE_TYPES.iter().find(|upgrade| {
+            let cost = view.definitions().upgrade(upgrade).cost;
+            view.can_research(upgrade) && player.minerals - cost.minerals >= RESEARCH_RESERVE
+        });
+        
+        upgrade.map(|upgrade| PlayerCommand::new(player_id, vec![center], Command::Research { upgrade: upgrade.clone() }))
+    }
+}
+
+// Whether a player has what a building needs: its prerequisites and its cost
+fn can_build(view: &PlayerView, building_type: &BuildingType) -> bool {
+    let definition = view.definitions().building(building_type);
+    view.has_prerequisites(&definition.prerequisites)
+        && view.player().is_some_and(|player| {
+            player.minerals >= definition.cost.minerals && player.energy >= definition.cost.energy
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::simulation::Simulation;
+    use crate::game::definitions::{Definitions, DEFINITIONS_PATH};
+    use crate::game::simulation::{TICK_DT, TICK_RATE};
+
+    #[test]
+    fn orders_wait_for_the_tick() {
+        let definitions = Definitions::load(DEFINITIONS_PATH).unwrap();
+        let mut simulation = Simulation::new(3, definitions);
+        let mut ai = AIController::new(1, 3);
+
+        // Idle workers get sent to gather, but only once the commands are ticked in
+        let commands: Vec<PlayerCommand> = (0..30).flat_map(|_| ai.update(&simulation.view(1), TICK_DT)).collect();
+        assert!(!commands.is_empty());
+        assert!(commands.iter().all(|command| command.player_id == 1));
+        assert!(simulation.units.iter().all(|unit| unit.orders.is_empty()));
+
+        simulation.tick(TICK_DT, &commands);
+        assert!(simulation.units.iter().any(|unit| unit.player_id == 1 && matches!(unit.orders.front(), Some(Command::Gather { .. }))));
+    }
+
+    // Both sides run by the AI, recording every order it hands the simulation
+    fn ai_match(seed: u64, ticks: usize) -> (Vec<Vec<PlayerCommand>>, String) {
+        let definitions = Definitions::load(DEFINITIONS_PATH).unwrap();
+        let mut simulation = Simulation::new(seed, definitions);
+        let mut ais = [AIController::new(0, seed), AIController::new(1, seed + 1)];
+        let mut recorded = Vec::new();
+        for _ in 0..ticks {
+            let commands: Vec<PlayerCommand> = ais.iter_mut()
+                .flat_map(|ai| ai.update(&simulation.view(ai.player_id()), TICK_DT))
+                .collect();
+            simulation.tick(TICK_DT, &commands);
+            recorded.push(commands);
+        }
+        (recorded, format!("{:?} {:?}", simulation.units, simulation.players))
+    }
+
+    #[test]
+    fn ai_matches_replay_from_their_commands() {
+        let (recorded, state) = ai_match(8, TICK_RATE as usize * 90);
+        assert!(recorded.iter().any(|commands| commands.iter().any(|command| matches!(command.command, Command::Build { .. }))));
+
+        // Replaying the orders alone, without the AI, lands in the same place
+        let mut replay = Simulation::new(8, Definitions::load(DEFINITIONS_PATH).unwrap());
+        for commands in &recorded {
+            replay.tick(TICK_DT, commands);
+        }
+        assert_eq!(format!("{:?} {:?}", replay.units, replay.players), state);
     }
 }
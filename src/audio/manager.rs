@@ -93,3 +93,9 @@ impl AudioManager {
         self.current_music.as_deref()
     }
 }
+
+impl Default for AudioManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
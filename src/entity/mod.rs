@@ -1,7 +1,11 @@
+use std::collections::VecDeque;
 use serde::{Deserialize, Serialize};
 use macroquad::prelude::*;
+use crate::game::commands::{Command, RallyPoint};
+use crate::game::definitions::UnitDefinition;
+use crate::game::types::ResourceType;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum UnitType {
     Worker,
     Fighter,
@@ -11,12 +15,68 @@ pub enum UnitType {
     Headquarters,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BuildingType {
     Barracks,
     Factory,
     ResourceDepot,
     DefenseTurret,
+    ResearchCenter,
+    SupplyDepot,
+}
+
+/// Improvements a player researches once at a Research Center.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UpgradeType {
+    InfantryWeapons,
+    InfantryArmor,
+    VehiclePlating,
+    TankCannons,
+}
+
+/// The kind of damage a unit deals; how much of it lands depends on the
+/// target's `ArmorType`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Normal,
+    /// Good against light units, poor against anything sturdier.
+    Piercing,
+    /// Heavy shells that tear through buildings.
+    Siege,
+}
+
+/// How a unit is protected, for damage multipliers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ArmorType {
+    #[default]
+    Light,
+    Heavy,
+    Structure,
+}
+
+/// Where a worker is in its round trip between a resource node and a drop-off.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GatherPhase {
+    /// Walking to the node.
+    ToNode,
+    /// Standing at the node, with the seconds mined so far.
+    Mining(f32),
+    /// Carrying a load back to the closest drop-off.
+    Returning,
+}
+
+/// How a unit reacts to enemies when it has no orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stance {
+    /// Chase and fight any enemy that comes into sight.
+    Aggressive,
+    /// Fight enemies that come close, then walk back to where it stood.
+    Defensive,
+    /// Never move on its own, but shoot whatever comes into range.
+    HoldPosition,
+    /// Never attack unless ordered to.
+    Passive,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,53 +87,168 @@ pub struct Unit {
     pub y: f32,
     pub health: f32,
     pub max_health: f32,
+    // Flat reduction applied to every hit taken
+    pub armor: f32,
+    pub armor_type: ArmorType,
     pub player_id: usize, // Changed from u8 to usize
-    pub speed: f32,
+    pub speed: f32, // Pixels per second
     pub target_x: Option<f32>,
     pub target_y: Option<f32>,
+    // Remaining waypoints toward the target, planned by the navigation grid
+    pub path: Vec<(f32, f32)>,
+    pub path_goal: Option<(f32, f32)>,
+    // Shared flow field this unit follows for a group move order
+    pub flow_field: Option<u32>,
+    // Orders still to carry out; the front one is in progress
+    pub orders: VecDeque<Command>,
+    // Where the current order was started from, `None` until it starts
+    pub order_origin: Option<(f32, f32)>,
+    // Enemy this unit is locked on to; it won't shoot at anything else meanwhile
+    pub attack_target: Option<u32>,
+    pub stance: Stance,
+    // Enemy this unit last fired at, until it has nothing in range
+    pub last_target: Option<u32>,
+    // Where a unit without orders stands guard, for stances that return to it
+    pub idle_position: Option<(f32, f32)>,
     pub attack_damage: f32,
+    pub damage_type: DamageType,
+    // Speed of its shots; zero for hits that land at once
+    pub projectile_speed: f32,
+    pub splash_radius: f32,
+    // Chance a shot at a moving target misses
+    pub moving_miss_chance: f32,
     pub attack_range: f32,
     pub attack_cooldown: f32,
     pub current_cooldown: f32,
+    pub sight: f32,
+    pub radius: f32,
+    pub footprint: f32,
     pub current_resources: Option<u32>,
     pub resource_capacity: Option<u32>,
+    // What the carried resources are, `None` while empty-handed
+    pub carried_resource: Option<ResourceType>,
+    // Node this worker last mined, which it goes back to after dropping off
+    pub gather_node: Option<usize>,
+    // Progress of the current gather order, `None` without one
+    pub gather_phase: Option<GatherPhase>,
+    // Seconds of mining it takes to pick up a load
+    pub gather_time: f32,
     // Add building-related fields
     pub building_type: Option<BuildingType>,
+    // From 0 to 1 while under construction, `None` once finished
     pub construction_progress: Option<f32>,
+    // Units paid for and waiting to be trained here, the front one in progress
+    pub production_queue: VecDeque<UnitType>,
+    // Seconds spent training the front of the production queue
+    pub production_progress: f32,
+    // Where units trained here head off to, if anywhere
+    pub rally_point: Option<RallyPoint>,
+    // Upgrade being researched here, if any
+    pub research: Option<UpgradeType>,
+    // Seconds spent on the research so far
+    pub research_progress: f32,
 }
 
 impl Unit {
-    pub fn new(id: u32, unit_type: UnitType, x: f32, y: f32, player_id: u8) -> Self {
-        let (health, speed, attack_damage, attack_range, attack_cooldown, current_resources, resource_capacity) = match unit_type {
-            UnitType::Worker => (50.0, 2.0, 5.0, 20.0, 2.0, Some(0), Some(50)),
-            UnitType::Fighter => (80.0, 3.0, 15.0, 30.0, 1.5, None, None),
-            UnitType::Ranger => (60.0, 2.5, 20.0, 50.0, 2.0, None, None),
-            UnitType::Tank => (150.0, 1.5, 30.0, 25.0, 3.0, None, None),
-            UnitType::Building => (200.0, 0.0, 0.0, 0.0, 0.0, None, None),
-            UnitType::Headquarters => (500.0, 0.0, 0.0, 0.0, 0.0, None, None),
-        };
+    /// A unit with the stats in `definition`, which should be the one
+    /// `Definitions::for_unit` gives for its type.
+    pub fn new(id: u32, unit_type: UnitType, x: f32, y: f32, player_id: u8, definition: &UnitDefinition) -> Self {
+        // Workers get on with gathering rather than picking fights
+        let stance = if unit_type == UnitType::Worker { Stance::Passive } else { Stance::Aggressive };
 
         Self {
             id,
             unit_type,
             x,
             y,
-            health,
-            max_health: health,
+            health: definition.health,
+            max_health: definition.health,
+            armor: definition.armor,
+            armor_type: definition.armor_type,
             player_id: player_id as usize, // Convert to usize
-            speed,
+            speed: definition.speed,
             target_x: None,
             target_y: None,
-            attack_damage,
-            attack_range,
-            attack_cooldown,
+            path: Vec::new(),
+            path_goal: None,
+            flow_field: None,
+            orders: VecDeque::new(),
+            order_origin: None,
+            attack_target: None,
+            stance,
+            last_target: None,
+            idle_position: None,
+            attack_damage: definition.attack_damage,
+            damage_type: definition.damage_type,
+            projectile_speed: definition.projectile_speed,
+            splash_radius: definition.splash_radius,
+            moving_miss_chance: definition.moving_miss_chance,
+            attack_range: definition.attack_range,
+            attack_cooldown: definition.attack_cooldown,
             current_cooldown: 0.0,
-            current_resources,
-            resource_capacity,
+            sight: definition.sight,
+            radius: definition.radius,
+            footprint: definition.footprint,
+            current_resources: definition.resource_capacity.map(|_| 0),
+            resource_capacity: definition.resource_capacity,
+            carried_resource: None,
+            gather_node: None,
+            gather_phase: None,
+            gather_time: definition.gather_time,
             building_type: None,
             construction_progress: None,
+            production_queue: VecDeque::new(),
+            production_progress: 0.0,
+            rally_point: None,
+            research: None,
+            research_progress: 0.0,
         }
     }
+
+    /// Radius of ground this unit blocks for pathfinding; zero for mobile units.
+    pub fn footprint_radius(&self) -> f32 {
+        self.footprint
+    }
+
+    /// Radius of the circle this unit physically occupies; other units are
+    /// kept outside it. Matches the size the unit is drawn at.
+    pub fn collision_radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// How far this unit reveals the fog of war around it.
+    pub fn sight_radius(&self) -> f32 {
+        self.sight
+    }
+
+    /// Whether the unit is on its way somewhere.
+    pub fn is_moving(&self) -> bool {
+        self.speed > 0.0 && self.target_x.is_some()
+    }
+
+    /// Whether the unit is finished and does its job; foundations don't.
+    pub fn is_complete(&self) -> bool {
+        self.construction_progress.is_none()
+    }
+
+    /// Drop every queued order and stop where the unit stands.
+    pub fn clear_orders(&mut self) {
+        self.orders.clear();
+        self.order_origin = None;
+        self.attack_target = None;
+        self.gather_phase = None;
+        self.gather_node = None;
+        self.clear_target();
+    }
+
+    /// Clear the current move order and any planned path.
+    pub fn clear_target(&mut self) {
+        self.target_x = None;
+        self.target_y = None;
+        self.path.clear();
+        self.path_goal = None;
+        self.flow_field = None;
+    }
 }
 
 // Create a serializable color wrapper
@@ -107,6 +282,12 @@ pub struct Player {
     pub id: usize, // Changed from u8 to usize
     pub minerals: i32,
     pub energy: i32,
+    // Upgrades finished so far
+    pub upgrades: Vec<UpgradeType>,
+    // Supply taken by units alive or queued, and what finished buildings
+    // provide, recounted every tick
+    pub supply_used: u32,
+    pub supply_cap: u32,
     #[serde(with = "color_serde")]
     pub color: Color,
     pub is_ai: bool,
@@ -1,13 +1,84 @@
-use crate::entity::{UnitType, BuildingType};
+use serde::{Deserialize, Serialize};
+use crate::entity::{BuildingType, Stance, UnitType, UpgradeType};
 
-#[derive(Clone, Debug, PartialEq)]
+/// An order for a group of units. Everything but `Train`, `CancelTraining`,
+/// `SetRallyPoint`, `Research`, `CancelResearch`, `Stop`, `SetStance` and
+/// `CancelConstruction` also goes into each unit's order queue.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Command {
-    #[allow(dead_code)]
     Move { x: f32, y: f32 },
+    /// Chase one unit until it dies or goes out of sight.
     Attack { target_id: u32 },
+    /// Move to a point, fighting any enemy seen on the way.
+    AttackMove { x: f32, y: f32 },
+    /// Mine a resource node by id and carry loads to the closest drop-off,
+    /// until it and every node of its kind nearby run dry.
     Gather { resource_id: usize },
+    /// Walk to a spot, lay a foundation there and build it.
     Build { building_type: BuildingType, x: f32, y: f32 },
-    #[allow(dead_code)]
+    /// Work on one of the player's unfinished buildings.
+    Construct { target_id: u32 },
+    /// Tear down unfinished buildings and get part of their cost back.
+    CancelConstruction,
+    /// Walk back and forth between where the order starts and the point,
+    /// fighting any enemy seen on the way.
+    Patrol { x: f32, y: f32 },
+    /// Follow a friendly unit and fight enemies that come near it.
+    Guard { target_id: u32 },
+    SetStance { stance: Stance },
+    /// Queue a unit at whichever selected building trains it and has the
+    /// shortest queue, paying for it up front.
     Train { unit_type: UnitType },
+    /// Take a unit out of a building's production queue and get its cost back.
+    CancelTraining { slot: usize },
+    /// Choose where units trained at the selected buildings head off to.
+    SetRallyPoint { rally_point: RallyPoint },
+    /// Start researching an upgrade at an idle selected Research Center,
+    /// paying for it up front.
+    Research { upgrade: UpgradeType },
+    /// Stop the research at the selected Research Centers and get its cost back.
+    CancelResearch,
     Stop,
 }
+
+/// Where units trained at a building go once they come out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RallyPoint {
+    Position { x: f32, y: f32 },
+    /// Follow one of the player's units.
+    Unit { target_id: u32 },
+    /// Workers start gathering from the node; anything else walks to it.
+    Resource { resource_id: usize },
+}
+
+/// A command issued by a player to a group of their units.
+///
+/// This is the only way input (mouse, UI buttons, AI, network) changes the
+/// simulation, so a match can be replayed from its command stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerCommand {
+    pub player_id: usize,
+    pub unit_ids: Vec<u32>,
+    pub command: Command,
+    /// Append to the units' order queues instead of replacing them (shift-click).
+    pub queued: bool,
+}
+
+impl PlayerCommand {
+    pub fn new(player_id: usize, unit_ids: Vec<u32>, command: Command) -> Self {
+        Self {
+            player_id,
+            unit_ids,
+            command,
+            queued: false,
+        }
+    }
+
+    /// A command that runs after whatever the units were already told to do.
+    pub fn queued(player_id: usize, unit_ids: Vec<u32>, command: Command) -> Self {
+        Self {
+            queued: true,
+            ..Self::new(player_id, unit_ids, command)
+        }
+    }
+}
@@ -0,0 +1,557 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::entity::{ArmorType, BuildingType, DamageType, Unit, UnitType, UpgradeType};
+
+/// Version written to and expected in definition files. Bump it whenever
+/// the format changes in a way older loaders can't read.
+pub const DEFINITIONS_FORMAT_VERSION: u32 = 1;
+
+/// Where the game reads its definitions from at startup.
+pub const DEFINITIONS_PATH: &str = "assets/definitions.json";
+
+/// Minerals and energy something costs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cost {
+    #[serde(default)]
+    pub minerals: i32,
+    #[serde(default)]
+    pub energy: i32,
+}
+
+// Minerals alone, or minerals/energy when it takes both
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.energy > 0 {
+            write!(f, "{}/{}", self.minerals, self.energy)
+        } else {
+            write!(f, "{}", self.minerals)
+        }
+    }
+}
+
+/// What trains a unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Producer {
+    Headquarters,
+    Building(BuildingType),
+}
+
+impl Producer {
+    /// Whether `unit` is this kind of producer, finished or not.
+    pub fn matches(&self, unit: &Unit) -> bool {
+        match self {
+            Producer::Headquarters => unit.unit_type == UnitType::Headquarters,
+            Producer::Building(building_type) => {
+                unit.unit_type == UnitType::Building && unit.building_type.as_ref() == Some(building_type)
+            },
+        }
+    }
+}
+
+/// Stats for one kind of unit or building; units copy them when spawned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitDefinition {
+    pub health: f32,
+    /// Flat reduction applied to every hit taken.
+    #[serde(default)]
+    pub armor: f32,
+    #[serde(default)]
+    pub armor_type: ArmorType,
+    /// Pixels per second; zero for anything that can't move.
+    #[serde(default)]
+    pub speed: f32,
+    #[serde(default)]
+    pub attack_damage: f32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    /// Pixels per second its shots fly at; zero for hits that land at once.
+    #[serde(default)]
+    pub projectile_speed: f32,
+    /// Radius around where a shot lands that nearby enemies are hurt in.
+    #[serde(default)]
+    pub splash_radius: f32,
+    /// Chance from 0 to 1 that a shot at a moving target misses.
+    #[serde(default)]
+    pub moving_miss_chance: f32,
+    #[serde(default)]
+    pub attack_range: f32,
+    /// Seconds between attacks.
+    #[serde(default)]
+    pub attack_cooldown: f32,
+    /// How far it reveals the fog of war.
+    pub sight: f32,
+    /// Radius of the circle it physically occupies.
+    pub radius: f32,
+    /// Radius of ground it blocks for pathfinding; zero for mobile units.
+    #[serde(default)]
+    pub footprint: f32,
+    #[serde(default)]
+    pub cost: Cost,
+    /// Supply it takes up while alive or waiting to be trained.
+    #[serde(default)]
+    pub supply: u32,
+    /// Supply cap it adds for its player once finished.
+    #[serde(default)]
+    pub supply_provided: u32,
+    /// Seconds to train or construct.
+    #[serde(default)]
+    pub build_time: f32,
+    /// What trains it, for units that can be trained.
+    #[serde(default)]
+    pub producer: Option<Producer>,
+    /// Buildings the player needs finished before they can get one.
+    #[serde(default)]
+    pub prerequisites: Vec<BuildingType>,
+    /// How much it carries, for units that gather.
+    #[serde(default)]
+    pub resource_capacity: Option<u32>,
+    /// Seconds of mining at a node to pick up a load, for units that gather.
+    #[serde(default)]
+    pub gather_time: f32,
+    /// Whether workers can drop resources off here once it's finished.
+    #[serde(default)]
+    pub drop_off: bool,
+}
+
+/// What an upgrade costs and what it improves once researched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpgradeDefinition {
+    pub cost: Cost,
+    /// Seconds of research at a Research Center.
+    pub research_time: f32,
+    /// Buildings the player needs finished before researching it.
+    #[serde(default)]
+    pub prerequisites: Vec<BuildingType>,
+    /// Unit types that get the bonuses.
+    pub applies_to: Vec<UnitType>,
+    /// Added to each affected unit's attack damage.
+    #[serde(default)]
+    pub attack_damage: f32,
+    /// Added to each affected unit's armor.
+    #[serde(default)]
+    pub armor: f32,
+}
+
+/// Every unit and building stat in the game, loaded from
+/// `assets/definitions.json`.
+///
+/// The simulation holds the one copy every other system asks for costs,
+/// build times, producers and prerequisites.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Definitions {
+    pub version: u32,
+    /// Everything but `UnitType::Building`, whose stats depend on its
+    /// `BuildingType`.
+    pub units: HashMap<UnitType, UnitDefinition>,
+    pub buildings: HashMap<BuildingType, UnitDefinition>,
+    pub upgrades: HashMap<UpgradeType, UpgradeDefinition>,
+    /// How much of each damage type lands on each armor type; pairs left
+    /// out deal full damage.
+    pub damage_multipliers: HashMap<DamageType, HashMap<ArmorType, f32>>,
+    /// Highest supply cap a player can reach, however many supply
+    /// buildings they have.
+    pub max_supply: u32,
+}
+
+// Every unit type with a definition of its own
+const DEFINED_UNIT_TYPES: [UnitType; 5] = [
+    UnitType::Worker,
+    UnitType::Fighter,
+    UnitType::Ranger,
+    UnitType::Tank,
+    UnitType::Headquarters,
+];
+
+/// Every building type a worker can construct.
+pub const BUILDING_TYPES: [BuildingType; 6] = [
+    BuildingType::Barracks,
+    BuildingType::Factory,
+    BuildingType::ResourceDepot,
+    BuildingType::DefenseTurret,
+    BuildingType::ResearchCenter,
+    BuildingType::SupplyDepot,
+];
+
+/// Every unit type a building can train.
+pub const TRAINABLE_UNIT_TYPES: [UnitType; 4] = [
+    UnitType::Worker,
+    UnitType::Fighter,
+    UnitType::Ranger,
+    UnitType::Tank,
+];
+
+/// Every upgrade a Research Center can research.
+pub const UPGRADE_TYPES: [UpgradeType; 4] = [
+    UpgradeType::InfantryWeapons,
+    UpgradeType::InfantryArmor,
+    UpgradeType::VehiclePlating,
+    UpgradeType::TankCannons,
+];
+
+impl Definitions {
+    /// Read and validate a definitions file, e.g. `DEFINITIONS_PATH`.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let data = std::fs::read_to_string(path)?;
+        Self::from_json(&data)
+    }
+
+    /// Parse and validate definitions from JSON text.
+    pub fn from_json(data: &str) -> Result<Self, Box<dyn Error>> {
+        let definitions: Definitions = serde_json::from_str(data)?;
+        definitions.validate()?;
+        Ok(definitions)
+    }
+
+    /// Check every unit and building type is defined once with sensible
+    /// stats, and that producers and prerequisites make sense.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.version != DEFINITIONS_FORMAT_VERSION {
+            return Err(format!("unsupported definitions version {} (expected {})", self.version, DEFINITIONS_FORMAT_VERSION).into());
+        }
+        if self.units.contains_key(&UnitType::Building) {
+            return Err("Building has no stats of its own; define each building type under \"buildings\"".into());
+        }
+
+        for unit_type in &DEFINED_UNIT_TYPES {
+            let definition = self.units.get(unit_type)
+                .ok_or_else(|| format!("unit {:?} is not defined", unit_type))?;
+            validate_stats(definition).map_err(|e| format!("unit {:?}: {}", unit_type, e))?;
+        }
+        for building_type in &BUILDING_TYPES {
+            let definition = self.buildings.get(building_type)
+                .ok_or_else(|| format!("building {:?} is not defined", building_type))?;
+            validate_stats(definition).map_err(|e| format!("building {:?}: {}", building_type, e))?;
+            if definition.footprint <= 0.0 || definition.build_time <= 0.0 {
+                return Err(format!("building {:?}: footprint and build time must be positive", building_type).into());
+            }
+            if definition.producer.is_some() {
+                return Err(format!("building {:?}: buildings are built by workers, not trained", building_type).into());
+            }
+        }
+
+        for unit_type in &TRAINABLE_UNIT_TYPES {
+            let definition = &self.units[unit_type];
+            if definition.producer.is_none() || definition.build_time <= 0.0 {
+                return Err(format!("unit {:?}: needs a producer and a positive build time", unit_type).into());
+            }
+            if definition.speed <= 0.0 {
+                return Err(format!("unit {:?}: trained units must be able to move", unit_type).into());
+            }
+        }
+        if self.max_supply == 0 {
+            return Err("max_supply must be positive".into());
+        }
+        if self.units[&UnitType::Headquarters].supply_provided == 0 {
+            return Err("unit Headquarters: has to provide supply, or nothing can be trained".into());
+        }
+        if self.units[&UnitType::Headquarters].producer.is_some() {
+            return Err("unit Headquarters: can't be trained".into());
+        }
+        let worker = &self.units[&UnitType::Worker];
+        if worker.resource_capacity.is_none_or(|capacity| capacity == 0) || worker.gather_time <= 0.0 {
+            return Err("unit Worker: needs a resource capacity and a positive gather time".into());
+        }
+        if !self.units[&UnitType::Headquarters].drop_off {
+            return Err("unit Headquarters: has to be a drop-off, or the first workers have nowhere to go".into());
+        }
+
+        for upgrade in &UPGRADE_TYPES {
+            let definition = self.upgrades.get(upgrade)
+                .ok_or_else(|| format!("upgrade {:?} is not defined", upgrade))?;
+            validate_upgrade(definition).map_err(|e| format!("upgrade {:?}: {}", upgrade, e))?;
+        }
+
+        let multipliers = self.damage_multipliers.iter()
+            .flat_map(|(damage_type, row)| row.iter().map(move |(armor_type, &multiplier)| (damage_type, armor_type, multiplier)));
+        for (damage_type, armor_type, multiplier) in multipliers {
+            if multiplier < 0.0 || !multiplier.is_finite() {
+                return Err(format!("damage multiplier {:?} against {:?} can't be negative", damage_type, armor_type).into());
+            }
+        }
+
+        // Every prerequisite chain has to end somewhere
+        for building_type in &BUILDING_TYPES {
+            let mut chain = vec![building_type.clone()];
+            self.check_prerequisites(&self.buildings[building_type].prerequisites, &mut chain)?;
+        }
+        Ok(())
+    }
+
+    // Walk prerequisites depth first, failing on one that leads back to the chain
+    fn check_prerequisites(&self, prerequisites: &[BuildingType], chain: &mut Vec<BuildingType>) -> Result<(), Box<dyn Error>> {
+        for prerequisite in prerequisites {
+            if chain.contains(prerequisite) {
+                return Err(format!("building {:?}: prerequisites loop back through {:?}", chain[0], prerequisite).into());
+            }
+            chain.push(prerequisite.clone());
+            self.check_prerequisites(&self.buildings[prerequisite].prerequisites, chain)?;
+            chain.pop();
+        }
+        Ok(())
+    }
+
+    /// The share of `damage_type` damage that lands on `armor_type`.
+    pub fn damage_multiplier(&self, damage_type: DamageType, armor_type: ArmorType) -> f32 {
+        self.damage_multipliers.get(&damage_type)
+            .and_then(|row| row.get(&armor_type))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Health a hit of `damage` takes off `target`: scaled by the
+    /// multiplier table, less armor, but never less than 1.
+    pub fn hit_damage(&self, damage: f32, damage_type: DamageType, target: &Unit) -> f32 {
+        let multiplier = self.damage_multiplier(damage_type, target.armor_type);
+        (damage * multiplier - target.armor).max(1.0)
+    }
+
+    /// Damage per second `attacker` deals to `target` while firing nonstop.
+    pub fn dps_against(&self, attacker: &Unit, target: &Unit) -> f32 {
+        if attacker.attack_damage <= 0.0 {
+            return 0.0;
+        }
+        self.hit_damage(attacker.attack_damage, attacker.damage_type, target) / attacker.attack_cooldown.max(0.1)
+    }
+
+    pub fn upgrade(&self, upgrade: &UpgradeType) -> &UpgradeDefinition {
+        &self.upgrades[upgrade]
+    }
+
+    /// Stats for a unit type; for `UnitType::Building` use `building`.
+    pub fn unit(&self, unit_type: &UnitType) -> &UnitDefinition {
+        &self.units[unit_type]
+    }
+
+    pub fn building(&self, building_type: &BuildingType) -> &UnitDefinition {
+        &self.buildings[building_type]
+    }
+
+    /// Stats for a unit as spawned: its building type's for buildings.
+    pub fn for_unit(&self, unit_type: &UnitType, building_type: Option<&BuildingType>) -> &UnitDefinition {
+        match (unit_type, building_type) {
+            (UnitType::Building, Some(building_type)) => self.building(building_type),
+            // A building of no particular type is as sturdy as a barracks
+            (UnitType::Building, None) => self.building(&BuildingType::Barracks),
+            (unit_type, _) => self.unit(unit_type),
+        }
+    }
+
+    /// What training a unit type costs; `None` for anything that can't be
+    /// trained.
+    pub fn training_cost(&self, unit_type: &UnitType) -> Option<Cost> {
+        self.units.get(unit_type)
+            .filter(|definition| definition.producer.is_some())
+            .map(|definition| definition.cost)
+    }
+
+    /// Whether `producer` is a finished building that trains `unit_type`.
+    pub fn can_train(&self, producer: &Unit, unit_type: &UnitType) -> bool {
+        producer.is_complete()
+            && self.units.get(unit_type)
+                .and_then(|definition| definition.producer.as_ref())
+                .is_some_and(|required| required.matches(producer))
+    }
+
+    /// Supply a unit type takes up; none for buildings.
+    pub fn supply(&self, unit_type: &UnitType) -> u32 {
+        self.units.get(unit_type).map_or(0, |definition| definition.supply)
+    }
+
+    /// Whether workers can drop resources off at `unit`, finished or not.
+    pub fn is_drop_off(&self, unit: &Unit) -> bool {
+        self.for_unit(&unit.unit_type, unit.building_type.as_ref()).drop_off
+    }
+
+    /// Whether `unit` is a building that trains anything, finished or not.
+    pub fn is_production_building(&self, unit: &Unit) -> bool {
+        self.units.values()
+            .filter_map(|definition| definition.producer.as_ref())
+            .any(|producer| producer.matches(unit))
+    }
+}
+
+// Checks shared by units and buildings
+fn validate_stats(definition: &UnitDefinition) -> Result<(), String> {
+    if definition.health <= 0.0 || definition.radius <= 0.0 {
+        return Err("health and radius must be positive".to_string());
+    }
+    let non_negative = [
+        definition.armor,
+        definition.speed,
+        definition.attack_damage,
+        definition.attack_range,
+        definition.attack_cooldown,
+        definition.sight,
+        definition.footprint,
+        definition.build_time,
+        definition.gather_time,
+        definition.projectile_speed,
+        definition.splash_radius,
+    ];
+    if non_negative.iter().any(|&value| value < 0.0 || !value.is_finite()) {
+        return Err("stats can't be negative".to_string());
+    }
+    if !(0.0..=1.0).contains(&definition.moving_miss_chance) {
+        return Err("moving miss chance must be between 0 and 1".to_string());
+    }
+    if definition.attack_damage > 0.0 && definition.attack_cooldown <= 0.0 {
+        return Err("units that attack need a positive attack cooldown".to_string());
+    }
+    if definition.cost.minerals < 0 || definition.cost.energy < 0 {
+        return Err("costs can't be negative".to_string());
+    }
+    Ok(())
+}
+
+fn validate_upgrade(definition: &UpgradeDefinition) -> Result<(), String> {
+    if definition.research_time <= 0.0 || !definition.research_time.is_finite() {
+        return Err("research time must be positive".to_string());
+    }
+    if definition.cost.minerals < 0 || definition.cost.energy < 0 {
+        return Err("costs can't be negative".to_string());
+    }
+    if definition.applies_to.is_empty() || !definition.applies_to.iter().all(|unit_type| TRAINABLE_UNIT_TYPES.contains(unit_type)) {
+        return Err("has to apply to at least one unit type, and only ones that can be trained".to_string());
+    }
+    if [definition.attack_damage, definition.armor].iter().any(|&bonus| bonus < 0.0 || !bonus.is_finite()) {
+        return Err("bonuses can't be negative".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shipped() -> Definitions {
+        Definitions::load(DEFINITIONS_PATH).expect("shipped definitions are valid")
+    }
+
+    #[test]
+    fn shipped_definitions_round_trip() {
+        let definitions = shipped();
+        let json = serde_json::to_string(&definitions).unwrap();
+        let loaded = Definitions::from_json(&json).unwrap();
+        assert_eq!(loaded.unit(&UnitType::Tank).health, definitions.unit(&UnitType::Tank).health);
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let mut definitions = shipped();
+        definitions.version = DEFINITIONS_FORMAT_VERSION + 1;
+        assert_eq!(
+            definitions.validate().unwrap_err().to_string(),
+            format!("unsupported definitions version {} (expected {})", DEFINITIONS_FORMAT_VERSION + 1, DEFINITIONS_FORMAT_VERSION),
+        );
+    }
+
+    #[test]
+    fn rejects_missing_types() {
+        let mut definitions = shipped();
+        definitions.units.remove(&UnitType::Tank);
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "unit Tank is not defined");
+
+        let mut definitions = shipped();
+        definitions.buildings.remove(&BuildingType::Factory);
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "building Factory is not defined");
+
+        let mut definitions = shipped();
+        definitions.upgrades.remove(&UpgradeType::TankCannons);
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "upgrade TankCannons is not defined");
+    }
+
+    #[test]
+    fn rejects_bad_stats() {
+        let mut definitions = shipped();
+        definitions.units.get_mut(&UnitType::Fighter).unwrap().health = 0.0;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "unit Fighter: health and radius must be positive");
+
+        let mut definitions = shipped();
+        definitions.units.get_mut(&UnitType::Ranger).unwrap().attack_range = -1.0;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "unit Ranger: stats can't be negative");
+
+        let mut definitions = shipped();
+        definitions.units.get_mut(&UnitType::Tank).unwrap().moving_miss_chance = 1.5;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "unit Tank: moving miss chance must be between 0 and 1");
+
+        let mut definitions = shipped();
+        definitions.units.get_mut(&UnitType::Worker).unwrap().cost.minerals = -50;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "unit Worker: costs can't be negative");
+
+        let mut definitions = shipped();
+        definitions.buildings.get_mut(&BuildingType::Factory).unwrap().footprint = 0.0;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "building Factory: footprint and build time must be positive");
+
+        let mut definitions = shipped();
+        definitions.max_supply = 0;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "max_supply must be positive");
+    }
+
+    #[test]
+    fn rejects_units_that_cannot_be_trained() {
+        let mut definitions = shipped();
+        definitions.units.get_mut(&UnitType::Tank).unwrap().producer = None;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "unit Tank: needs a producer and a positive build time");
+
+        let mut definitions = shipped();
+        definitions.units.get_mut(&UnitType::Fighter).unwrap().speed = 0.0;
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "unit Fighter: trained units must be able to move");
+    }
+
+    #[test]
+    fn rejects_negative_damage_multipliers() {
+        let mut definitions = shipped();
+        definitions.damage_multipliers.entry(DamageType::Siege).or_default().insert(ArmorType::Light, -0.5);
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "damage multiplier Siege against Light can't be negative");
+    }
+
+    #[test]
+    fn rejects_prerequisite_loops() {
+        let mut definitions = shipped();
+        definitions.buildings.get_mut(&BuildingType::Barracks).unwrap().prerequisites = vec![BuildingType::Factory];
+        assert_eq!(definitions.validate().unwrap_err().to_string(), "building Barracks: prerequisites loop back through Barracks");
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let error = Definitions::from_json("{ \"version\": 1 }").unwrap_err().to_string();
+        assert!(error.starts_with("missing field"), "{}", error);
+        assert!(Definitions::from_json("not json").is_err());
+    }
+
+    fn unit_of(definitions: &Definitions, unit_type: UnitType) -> Unit {
+        Unit::new(1, unit_type.clone(), 0.0, 0.0, 0, definitions.unit(&unit_type))
+    }
+
+    #[test]
+    fn hits_are_scaled_by_the_multiplier_table_less_armor() {
+        let definitions = shipped();
+        let fighter = unit_of(&definitions, UnitType::Fighter);
+        let ranger = unit_of(&definitions, UnitType::Ranger);
+        let tank = unit_of(&definitions, UnitType::Tank);
+        let barracks = Unit::new(2, UnitType::Building, 0.0, 0.0, 1, definitions.building(&BuildingType::Barracks));
+
+        // Rangers shred light infantry and bounce off tanks; tanks flatten buildings
+        assert_eq!(definitions.hit_damage(ranger.attack_damage, ranger.damage_type, &fighter), 20.0 * 1.5 - 1.0);
+        assert_eq!(definitions.hit_damage(ranger.attack_damage, ranger.damage_type, &tank), 20.0 * 0.5 - 3.0);
+        assert_eq!(definitions.hit_damage(tank.attack_damage, tank.damage_type, &barracks), 30.0 * 2.0 - 2.0);
+        assert_eq!(definitions.hit_damage(fighter.attack_damage, fighter.damage_type, &barracks), 15.0 * 0.5 - 2.0);
+
+        // Armor never soaks up a whole hit
+        assert_eq!(definitions.hit_damage(2.0, DamageType::Normal, &tank), 1.0);
+    }
+
+    #[test]
+    fn dps_accounts_for_cooldown_and_counters() {
+        let definitions = shipped();
+        let fighter = unit_of(&definitions, UnitType::Fighter);
+        let ranger = unit_of(&definitions, UnitType::Ranger);
+        let barracks = Unit::new(2, UnitType::Building, 0.0, 0.0, 1, definitions.building(&BuildingType::Barracks));
+
+        assert_eq!(definitions.dps_against(&ranger, &fighter), (20.0 * 1.5 - 1.0) / 2.0);
+        assert_eq!(definitions.dps_against(&fighter, &ranger), 15.0 / 1.5);
+        assert_eq!(definitions.dps_against(&barracks, &fighter), 0.0);
+    }
+}
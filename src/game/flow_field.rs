@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use crate::game::navigation::NavGrid;
+
+/// Units at or above this count share a flow field for a single move order.
+pub const GROUP_MOVE_THRESHOLD: usize = 20;
+
+/// Spacing kept between units when a group order squeezes its formation.
+pub const FORMATION_SPACING: f32 = 36.0;
+
+/// Distance-to-goal for every cell of the navigation grid.
+///
+/// Built once per group move order; every unit in the group just follows the
+/// downhill direction from whatever cell it stands on, so a large army costs a
+/// single Dijkstra sweep instead of one A* query per unit.
+#[derive(Debug, Clone)]
+pub struct FlowField {
+    pub id: u32,
+    pub goal: (f32, f32),
+    // Followers switch to their own path once this close to the goal
+    pub arrival_radius: f32,
+    width: usize,
+    height: usize,
+    cell_size: f32,
+    distances: Vec<u32>,
+}
+
+impl FlowField {
+    pub fn build(id: u32, grid: &NavGrid, goal: (f32, f32)) -> Self {
+        let mut field = Self {
+            id,
+            goal,
+            arrival_radius: grid.cell_size * 2.0,
+            width: grid.width,
+            height: grid.height,
+            cell_size: grid.cell_size,
+            distances: vec![u32::MAX; grid.width * grid.height],
+        };
+        field.rebuild(grid);
+        field
+    }
+
+    /// Recompute distances, e.g. after buildings changed the grid.
+    pub fn rebuild(&mut self, grid: &NavGrid) {
+        self.width = grid.width;
+        self.height = grid.height;
+        self.cell_size = grid.cell_size;
+        self.distances = vec![u32::MAX; grid.width * grid.height];
+
+        let goal_cell = grid.world_to_cell(self.goal.0, self.goal.1);
+        let goal_cell = match grid.nearest_walkable(goal_cell.0, goal_cell.1) {
+            Some(cell) => cell,
+            None => return,
+        };
+
+        // Dijkstra with a re-queue on improvement; costs are small integers so a FIFO converges quickly
+        let mut queue = VecDeque::new();
+        self.distances[goal_cell.1 * self.width + goal_cell.0] = 0;
+        queue.push_back(goal_cell);
+
+        while let Some((cx, cy)) = queue.pop_front() {
+            let current = self.distances[cy * self.width + cx];
+
+            for (nx, ny, cost) in grid.neighbors(cx, cy) {
+                let index = ny * self.width + nx;
+                if current + cost < self.distances[index] {
+                    self.distances[index] = current + cost;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+    }
+
+    fn cell_at(&self, x: f32, y: f32) -> (usize, usize) {
+        let cx = (x / self.cell_size).floor().clamp(0.0, (self.width - 1) as f32) as usize;
+        let cy = (y / self.cell_size).floor().clamp(0.0, (self.height - 1) as f32) as usize;
+        (cx, cy)
+    }
+
+    /// Normalised direction to walk from a world position, or `None` when the
+    /// goal can't be reached from there.
+    pub fn direction_at(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let (cx, cy) = self.cell_at(x, y);
+        let mut best = self.distances[cy * self.width + cx];
+        let mut best_cell = None;
+
+        for dy in -1i32..=1 {
+            for dx in -1i32..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let nx = cx as i32 + dx;
+                let ny = cy as i32 + dy;
+                if nx < 0 || ny < 0 || nx >= self.width as i32 || ny >= self.height as i32 {
+                    continue;
+                }
+
+                // Don't slip diagonally past a blocked corner
+                if dx != 0 && dy != 0 &&
+                   (self.distances[cy * self.width + nx as usize] == u32::MAX ||
+                    self.distances[ny as usize * self.width + cx] == u32::MAX) {
+                    continue;
+                }
+
+                let distance = self.distances[ny as usize * self.width + nx as usize];
+                if distance < best {
+                    best = distance;
+                    best_cell = Some((nx as usize, ny as usize));
+                }
+            }
+        }
+
+        match best_cell {
+            Some((nx, ny)) => {
+                // Steer for the center of the next cell
+                let target_x = (nx as f32 + 0.5) * self.cell_size;
+                let target_y = (ny as f32 + 0.5) * self.cell_size;
+                let dx = target_x - x;
+                let dy = target_y - y;
+                let length = (dx * dx + dy * dy).sqrt();
+                if length > 0.0 {
+                    Some((dx / length, dy / length))
+                } else {
+                    None
+                }
+            },
+            // Already in the goal cell (or stranded); let the caller walk directly
+            None => None,
+        }
+    }
+}
+
+/// Per-unit destinations around `center` that keep the group's current shape,
+/// squeezed so the formation is no wider than the group size warrants.
+pub fn formation_targets(positions: &[(f32, f32)], center: (f32, f32)) -> Vec<(f32, f32)> {
+    if positions.is_empty() {
+        return Vec::new();
+    }
+
+    let count = positions.len() as f32;
+    let centroid_x = positions.iter().map(|p| p.0).sum::<f32>() / count;
+    let centroid_y = positions.iter().map(|p| p.1).sum::<f32>() / count;
+
+    let spread = positions.iter()
+        .map(|p| ((p.0 - centroid_x).powi(2) + (p.1 - centroid_y).powi(2)).sqrt())
+        .fold(0.0, f32::max);
+
+    // Roughly the radius of a disc that fits the group at the spacing we want
+    let max_radius = FORMATION_SPACING * count.sqrt() * 0.6;
+    let scale = if spread > max_radius { max_radius / spread } else { 1.0 };
+
+    positions.iter()
+        .map(|p| (
+            center.0 + (p.0 - centroid_x) * scale,
+            center.1 + (p.1 - centroid_y) * scale,
+        ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::navigation::NAV_CELL_SIZE;
+
+    #[test]
+    fn field_leads_around_a_wall() {
+        // Wall down column 10 with a gap at row 15
+        let mut grid = NavGrid::new(640.0, 640.0, NAV_CELL_SIZE);
+        for cy in 0..grid.height {
+            if cy != 15 {
+                grid.set_blocked(10, cy, true);
+            }
+        }
+        let goal = grid.cell_center(17, 2);
+        let field = FlowField::build(1, &grid, goal);
+
+        // Walk half a cell at a time; never onto the wall and into the goal cell
+        let (mut x, mut y) = grid.cell_center(2, 2);
+        for _ in 0..200 {
+            match field.direction_at(x, y) {
+                Some((dx, dy)) => {
+                    x += dx * grid.cell_size * 0.5;
+                    y += dy * grid.cell_size * 0.5;
+                    assert!(grid.is_walkable(x, y), "walked onto a blocked cell at ({}, {})", x, y);
+                },
+                None => break,
+            }
+        }
+        assert_eq!(grid.world_to_cell(x, y), grid.world_to_cell(goal.0, goal.1));
+    }
+
+    #[test]
+    fn no_direction_where_goal_is_unreachable() {
+        let mut grid = NavGrid::new(640.0, 640.0, NAV_CELL_SIZE);
+        for cy in 0..grid.height {
+            grid.set_blocked(10, cy, true);
+        }
+        let field = FlowField::build(1, &grid, grid.cell_center(17, 2));
+        let (x, y) = grid.cell_center(2, 2);
+        assert_eq!(field.direction_at(x, y), None);
+    }
+
+    #[test]
+    fn formation_keeps_shape_around_the_destination() {
+        let positions = [(100.0, 100.0), (140.0, 100.0), (120.0, 130.0)];
+        let targets = formation_targets(&positions, (500.0, 500.0));
+        assert_eq!(targets, vec![(480.0, 490.0), (520.0, 490.0), (500.0, 520.0)]);
+    }
+
+    #[test]
+    fn scattered_group_is_squeezed_together() {
+        let positions: Vec<(f32, f32)> = (0..20).map(|i| (i as f32 * 200.0, 0.0)).collect();
+        let targets = formation_targets(&positions, (1000.0, 1000.0));
+
+        let max_radius = FORMATION_SPACING * (positions.len() as f32).sqrt() * 0.6;
+        for target in &targets {
+            let distance = ((target.0 - 1000.0).powi(2) + (target.1 - 1000.0).powi(2)).sqrt();
+            assert!(distance <= max_radius + 0.01, "{:?} is {} from the center", target, distance);
+        }
+    }
+}
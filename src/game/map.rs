@@ -0,0 +1,251 @@
+use std::error::Error;
+use serde::{Deserialize, Serialize};
+use crate::game::terrain::{TerrainMap, TILE_SIZE};
+use crate::game::types::ResourceType;
+
+/// Version written to and expected in map files. Bump it whenever the
+/// format changes in a way older loaders can't read.
+pub const MAP_FORMAT_VERSION: u32 = 1;
+
+/// A map as stored on disk in `assets/maps`.
+///
+/// Terrain is one string per row of tiles using the characters from
+/// `TerrainType::to_char`, so maps stay readable and diffable by hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapData {
+    pub version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub width: f32,
+    pub height: f32,
+    #[serde(default = "default_tile_size")]
+    pub tile_size: f32,
+    pub terrain: Vec<String>,
+    pub start_locations: Vec<StartLocation>,
+    #[serde(default)]
+    pub resources: Vec<MapResource>,
+    #[serde(default)]
+    pub neutral_objects: Vec<NeutralObject>,
+}
+
+fn default_tile_size() -> f32 {
+    TILE_SIZE
+}
+
+/// Where a player's headquarters is placed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartLocation {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapResource {
+    pub x: f32,
+    pub y: f32,
+    pub resource_type: ResourceType,
+    pub amount: i32,
+    #[serde(default = "default_resource_radius")]
+    pub radius: f32,
+}
+
+fn default_resource_radius() -> f32 {
+    25.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NeutralObjectKind {
+    Boulder,
+    Ruins,
+}
+
+/// Ownerless scenery that blocks movement, like a boulder in a pass.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NeutralObject {
+    pub kind: NeutralObjectKind,
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+impl MapData {
+    /// Read and validate a map file.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let data = std::fs::read_to_string(path)?;
+        Self::from_json(&data)
+    }
+
+    /// Parse and validate a map from JSON text.
+    pub fn from_json(data: &str) -> Result<Self, Box<dyn Error>> {
+        let map: MapData = serde_json::from_str(data)?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Check the map is playable: a known version, terrain covering the whole
+    /// map, and every start location and resource on open ground.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.version != MAP_FORMAT_VERSION {
+            return Err(format!("unsupported map version {} (expected {})", self.version, MAP_FORMAT_VERSION).into());
+        }
+        if self.width <= 0.0 || self.height <= 0.0 || self.tile_size <= 0.0 {
+            return Err("map dimensions must be positive".into());
+        }
+
+        let terrain = self.terrain_map()?;
+        let expected_width = (self.width / self.tile_size).ceil() as usize;
+        let expected_height = (self.height / self.tile_size).ceil() as usize;
+        if terrain.width != expected_width || terrain.height != expected_height {
+            return Err(format!(
+                "terrain is {}x{} tiles but a {}x{} map needs {}x{}",
+                terrain.width, terrain.height, self.width, self.height, expected_width, expected_height
+            ).into());
+        }
+
+        if self.start_locations.is_empty() {
+            return Err("map has no start locations".into());
+        }
+        for (i, start) in self.start_locations.iter().enumerate() {
+            if !terrain.is_passable(start.x, start.y) {
+                return Err(format!("start location {} at ({}, {}) is not on open ground", i, start.x, start.y).into());
+            }
+        }
+
+        for (i, resource) in self.resources.iter().enumerate() {
+            if !terrain.is_passable(resource.x, resource.y) {
+                return Err(format!("resource {} at ({}, {}) is not on open ground", i, resource.x, resource.y).into());
+            }
+            if resource.amount <= 0 || resource.radius <= 0.0 {
+                return Err(format!("resource {} must have a positive amount and radius", i).into());
+            }
+        }
+
+        for (i, object) in self.neutral_objects.iter().enumerate() {
+            if terrain.terrain_at(object.x, object.y).is_none() || object.radius <= 0.0 {
+                return Err(format!("neutral object {} is off the map or has no size", i).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The terrain layer described by the map's rows.
+    pub fn terrain_map(&self) -> Result<TerrainMap, Box<dyn Error>> {
+        Ok(TerrainMap::from_rows(&self.terrain, self.tile_size)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4x4 tiles of grass with water in the top right corner
+    fn small_map() -> MapData {
+        MapData {
+            version: MAP_FORMAT_VERSION,
+            name: "Test".to_string(),
+            description: String::new(),
+            width: 512.0,
+            height: 512.0,
+            tile_size: 128.0,
+            terrain: vec!["..~~".to_string(), "..~~".to_string(), "....".to_string(), "....".to_string()],
+            start_locations: vec![StartLocation { x: 64.0, y: 64.0 }, StartLocation { x: 448.0, y: 448.0 }],
+            resources: vec![MapResource { x: 200.0, y: 400.0, resource_type: ResourceType::Minerals, amount: 1000, radius: 25.0 }],
+            neutral_objects: vec![NeutralObject { kind: NeutralObjectKind::Boulder, x: 250.0, y: 250.0, radius: 30.0 }],
+        }
+    }
+
+    #[test]
+    fn playable_map_round_trips() {
+        let map = small_map();
+        map.validate().unwrap();
+        let loaded = MapData::from_json(&map.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.terrain, map.terrain);
+        assert_eq!(loaded.start_locations.len(), 2);
+    }
+
+    #[test]
+    fn bundled_maps_load() {
+        for name in ["crossroads", "duel_at_the_ford", "great_ring"] {
+            let path = format!("assets/maps/{}.json", name);
+            if let Err(e) = MapData::load(&path) {
+                panic!("{} failed to load: {}", path, e);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let mut map = small_map();
+        map.version = MAP_FORMAT_VERSION + 1;
+        assert_eq!(map.validate().unwrap_err().to_string(), format!("unsupported map version {} (expected {})", MAP_FORMAT_VERSION + 1, MAP_FORMAT_VERSION));
+    }
+
+    #[test]
+    fn rejects_empty_dimensions() {
+        let mut map = small_map();
+        map.width = 0.0;
+        assert_eq!(map.validate().unwrap_err().to_string(), "map dimensions must be positive");
+    }
+
+    #[test]
+    fn rejects_terrain_not_covering_the_map() {
+        let mut map = small_map();
+        map.terrain.pop();
+        assert_eq!(map.validate().unwrap_err().to_string(), "terrain is 4x3 tiles but a 512x512 map needs 4x4");
+    }
+
+    #[test]
+    fn rejects_unknown_terrain() {
+        let mut map = small_map();
+        map.terrain[2] = "..?.".to_string();
+        assert_eq!(map.validate().unwrap_err().to_string(), "unknown terrain '?' at tile (2, 2)");
+    }
+
+    #[test]
+    fn rejects_maps_without_starts() {
+        let mut map = small_map();
+        map.start_locations.clear();
+        assert_eq!(map.validate().unwrap_err().to_string(), "map has no start locations");
+    }
+
+    #[test]
+    fn rejects_start_in_the_water() {
+        let mut map = small_map();
+        map.start_locations[1] = StartLocation { x: 400.0, y: 100.0 };
+        assert_eq!(map.validate().unwrap_err().to_string(), "start location 1 at (400, 100) is not on open ground");
+    }
+
+    #[test]
+    fn rejects_resource_in_the_water() {
+        let mut map = small_map();
+        map.resources[0].x = 400.0;
+        map.resources[0].y = 100.0;
+        assert_eq!(map.validate().unwrap_err().to_string(), "resource 0 at (400, 100) is not on open ground");
+    }
+
+    #[test]
+    fn rejects_empty_resource() {
+        let mut map = small_map();
+        map.resources[0].amount = 0;
+        assert_eq!(map.validate().unwrap_err().to_string(), "resource 0 must have a positive amount and radius");
+    }
+
+    #[test]
+    fn rejects_object_off_the_map() {
+        let mut map = small_map();
+        map.neutral_objects[0].x = 900.0;
+        assert_eq!(map.validate().unwrap_err().to_string(), "neutral object 0 is off the map or has no size");
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let error = MapData::from_json("{ \"version\": 1 }").unwrap_err().to_string();
+        assert!(error.contains("missing field `name`"), "{}", error);
+    }
+}
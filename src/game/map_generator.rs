@@ -0,0 +1,320 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use crate::game::map::{MapData, MapResource, StartLocation, MAP_FORMAT_VERSION};
+use crate::game::rng::SimRng;
+use crate::game::terrain::{TerrainMap, TerrainType, TILE_SIZE};
+use crate::game::types::ResourceType;
+
+// Attempts at a fully connected layout before giving up on obstacles
+const MAX_ATTEMPTS: usize = 8;
+
+/// Knobs for `generate_map`.
+#[derive(Debug, Clone)]
+pub struct MapGeneratorSettings {
+    pub players: usize,
+    pub width: f32,
+    pub height: f32,
+    /// Roughly how much of the map is covered by lakes, rocks and woods, 0 to 1.
+    pub terrain_density: f32,
+    /// Multiplier on how much each resource node holds.
+    pub resource_density: f32,
+    /// Resource clusters per player besides the one at their base.
+    pub expansions_per_player: usize,
+}
+
+impl Default for MapGeneratorSettings {
+    fn default() -> Self {
+        Self {
+            players: 2,
+            width: 5120.0,
+            height: 4096.0,
+            terrain_density: 0.5,
+            resource_density: 1.0,
+            expansions_per_player: 2,
+        }
+    }
+}
+
+// A feature laid out for the first player, copied around the map for the others
+struct Feature {
+    angle: f32,
+    distance: f32,
+    radius: f32,
+    terrain: TerrainType,
+}
+
+/// Build a complete map from a seed; the same settings and seed always
+/// produce the same map.
+///
+/// Everything is laid out for one player's slice of the map and rotated
+/// around the center for the others, so every start is equally good.
+pub fn generate_map(settings: &MapGeneratorSettings, seed: u64) -> MapData {
+    let mut rng = SimRng::new(seed);
+    let players = settings.players.clamp(1, 8);
+    let center = (settings.width / 2.0, settings.height / 2.0);
+    // Bases and resources stay inside the inscribed circle so rotated copies never leave the map
+    let max_distance = settings.width.min(settings.height) / 2.0 - TILE_SIZE;
+    let corner_distance = (settings.width * settings.width + settings.height * settings.height).sqrt() / 2.0;
+    let start_distance = max_distance * 0.75;
+    let sector = 2.0 * PI / players as f32;
+    let base_angle = rng.gen_range(0.0, 2.0 * PI);
+
+    let at = |angle: f32, distance: f32| (center.0 + angle.cos() * distance, center.1 + angle.sin() * distance);
+
+    // Start locations evenly around the center
+    let start_locations: Vec<StartLocation> = (0..players)
+        .map(|player| {
+            let (x, y) = at(base_angle + sector * player as f32, start_distance);
+            StartLocation { x, y }
+        })
+        .collect();
+
+    // The first player's resources, relative to the map center
+    let mut resources = Vec::new();
+    let outward = base_angle;
+    let start = at(base_angle, start_distance);
+
+    // Main base: a mineral arc behind the headquarters and energy to either side
+    for offset in [-50.0f32, -30.0, -10.0, 10.0, 30.0, 50.0] {
+        let angle = outward + offset.to_radians();
+        resources.push(resource_at(start, angle, 260.0, ResourceType::Minerals, 1500, settings));
+    }
+    for offset in [-110.0f32, 110.0] {
+        let angle = outward + offset.to_radians();
+        resources.push(resource_at(start, angle, 300.0, ResourceType::Energy, 1200, settings));
+    }
+
+    // Expansions further into the player's slice, the first one close to home
+    for expansion in 0..settings.expansions_per_player {
+        let (angle, distance) = if expansion == 0 {
+            (base_angle + sector * 0.3, start_distance * 0.7)
+        } else {
+            (
+                base_angle + rng.gen_range(-sector * 0.4, sector * 0.4),
+                rng.gen_range(max_distance * 0.25, start_distance * 0.6),
+            )
+        };
+        let cluster = at(angle, distance);
+        let amount = rng.gen_range(1500, 2200);
+
+        for i in 0..4 {
+            let angle = i as f32 * PI / 2.0 + rng.gen_range(-0.3, 0.3);
+            resources.push(resource_at(cluster, angle, 90.0, ResourceType::Minerals, amount, settings));
+        }
+        resources.push(resource_at(cluster, rng.gen_range(0.0, 2.0 * PI), 160.0, ResourceType::Energy, amount * 3 / 4, settings));
+    }
+
+    // Copy the first player's resources around the map
+    let resources: Vec<MapResource> = (0..players)
+        .flat_map(|player| resources.iter().map(move |resource| rotate_resource(resource, center, sector * player as f32)))
+        .collect();
+
+    // Features may not touch a base or any resource node, on any player's side
+    let mut keep_clear: Vec<(f32, f32, f32)> = start_locations.iter().map(|start| (start.x, start.y, 450.0)).collect();
+    keep_clear.extend(resources.iter().map(|resource| (resource.x, resource.y, resource.radius + 100.0)));
+
+    let area = settings.width * settings.height;
+    let feature_count = (settings.terrain_density.clamp(0.0, 1.0) * area / 250_000.0 / players as f32) as usize;
+
+    let mut terrain = TerrainMap::new(settings.width, settings.height, TILE_SIZE);
+    for attempt in 0..MAX_ATTEMPTS {
+        let mut features = Vec::new();
+        for _ in 0..feature_count {
+            let feature = Feature {
+                angle: base_angle + rng.gen_range(0.0, sector),
+                distance: rng.gen_range(0.0, corner_distance),
+                radius: rng.gen_range(120.0, 380.0),
+                terrain: match rng.gen_range(0, 5) {
+                    0 => TerrainType::Water,
+                    1 => TerrainType::RockObstacle,
+                    2 => TerrainType::Trees,
+                    3 => TerrainType::Dirt,
+                    _ => TerrainType::Sand,
+                },
+            };
+
+            // Checking one copy is enough: the layout is the same around every start
+            let (x, y) = at(feature.angle, feature.distance);
+            let blocks_clear_zone = keep_clear.iter().any(|&(clear_x, clear_y, clear_radius)| {
+                ((clear_x - x).powi(2) + (clear_y - y).powi(2)).sqrt() < feature.radius + clear_radius
+            });
+            // Features may fill the corners as long as every copy lands on the map
+            let on_map = (0..players).all(|player| {
+                let (x, y) = at(feature.angle + sector * player as f32, feature.distance);
+                x >= 0.0 && y >= 0.0 && x <= settings.width && y <= settings.height
+            });
+            if !blocks_clear_zone && on_map {
+                features.push(feature);
+            }
+        }
+
+        // Give up on impassable features if no layout connects everyone
+        if attempt == MAX_ATTEMPTS - 1 {
+            features.retain(|feature| feature.terrain.is_passable());
+        }
+
+        terrain = TerrainMap::new(settings.width, settings.height, TILE_SIZE);
+        for player in 0..players {
+            let rotation = sector * player as f32;
+            for feature in &features {
+                let (x, y) = at(feature.angle + rotation, feature.distance);
+                // Lakes get a sandy shore and outcrops sit on rough ground
+                match feature.terrain {
+                    TerrainType::Water => terrain.paint_circle(x, y, feature.radius + TILE_SIZE, TerrainType::Sand),
+                    TerrainType::RockObstacle => terrain.paint_circle(x, y, feature.radius + TILE_SIZE, TerrainType::Rock),
+                    _ => {},
+                }
+            }
+            for feature in &features {
+                let (x, y) = at(feature.angle + rotation, feature.distance);
+                terrain.paint_circle(x, y, feature.radius, feature.terrain);
+            }
+        }
+
+        let targets: Vec<(f32, f32)> = start_locations.iter().map(|start| (start.x, start.y))
+            .chain(resources.iter().map(|resource| (resource.x, resource.y)))
+            .collect();
+        if all_connected(&terrain, &targets) {
+            break;
+        }
+    }
+
+    MapData {
+        version: MAP_FORMAT_VERSION,
+        name: format!("Generated {}p #{}", players, seed),
+        description: format!("Procedurally generated for {} players from seed {}.", players, seed),
+        width: settings.width,
+        height: settings.height,
+        tile_size: TILE_SIZE,
+        terrain: terrain.to_rows(),
+        start_locations,
+        resources,
+        neutral_objects: Vec::new(),
+    }
+}
+
+fn resource_at(
+    origin: (f32, f32),
+    angle: f32,
+    distance: f32,
+    resource_type: ResourceType,
+    amount: i32,
+    settings: &MapGeneratorSettings,
+) -> MapResource {
+    let radius = match resource_type {
+        ResourceType::Minerals => 25.0,
+        ResourceType::Energy => 20.0,
+    };
+
+    MapResource {
+        x: origin.0 + angle.cos() * distance,
+        y: origin.1 + angle.sin() * distance,
+        resource_type,
+        amount: ((amount as f32 * settings.resource_density) as i32).max(1),
+        radius,
+    }
+}
+
+fn rotate_resource(resource: &MapResource, center: (f32, f32), rotation: f32) -> MapResource {
+    let dx = resource.x - center.0;
+    let dy = resource.y - center.1;
+    let (sin, cos) = rotation.sin_cos();
+
+    MapResource {
+        x: center.0 + dx * cos - dy * sin,
+        y: center.1 + dx * sin + dy * cos,
+        ..resource.clone()
+    }
+}
+
+// Whether every point sits on passable ground reachable from the first one
+fn all_connected(terrain: &TerrainMap, points: &[(f32, f32)]) -> bool {
+    let tiles: Vec<(usize, usize)> = match points.iter().map(|&(x, y)| terrain.tile_at(x, y)).collect() {
+        Some(tiles) => tiles,
+        None => return false,
+    };
+    let first = match tiles.first() {
+        Some(&first) => first,
+        None => return true,
+    };
+
+    // Flood fill the passable tiles from the first point
+    let mut reached = vec![false; terrain.width * terrain.height];
+    let mut queue = VecDeque::new();
+    if terrain.get(first.0, first.1).is_some_and(TerrainType::is_passable) {
+        reached[first.1 * terrain.width + first.0] = true;
+        queue.push_back(first);
+    }
+
+    while let Some((tx, ty)) = queue.pop_front() {
+        for (dx, dy) in [(-1i32, 0i32), (1, 0), (0, -1), (0, 1)] {
+            let nx = tx as i32 + dx;
+            let ny = ty as i32 + dy;
+            if nx < 0 || ny < 0 {
+                continue;
+            }
+            let (nx, ny) = (nx as usize, ny as usize);
+            if terrain.get(nx, ny).is_some_and(TerrainType::is_passable) && !reached[ny * terrain.width + nx] {
+                reached[ny * terrain.width + nx] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    tiles.iter().all(|&(tx, ty)| reached[ty * terrain.width + tx])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::navigation::NavGrid;
+
+    #[test]
+    fn same_seed_same_map() {
+        let settings = MapGeneratorSettings::default();
+        let map = generate_map(&settings, 1234).to_json().unwrap();
+        assert_eq!(map, generate_map(&settings, 1234).to_json().unwrap());
+        assert_ne!(map, generate_map(&settings, 1235).to_json().unwrap());
+    }
+
+    #[test]
+    fn start_locations_reach_each_other() {
+        for players in [2, 3, 4] {
+            let settings = MapGeneratorSettings { players, ..MapGeneratorSettings::default() };
+            for seed in 0..5 {
+                let map = generate_map(&settings, seed);
+                if let Err(e) = map.validate() {
+                    panic!("{} players, seed {}: {}", players, seed, e);
+                }
+                assert_eq!(map.start_locations.len(), players);
+
+                let terrain = map.terrain_map().unwrap();
+                let grid = NavGrid::build(map.width, map.height, &terrain, &[]);
+                let home = (map.start_locations[0].x, map.start_locations[0].y);
+                for start in &map.start_locations[1..] {
+                    let path = grid.find_path(home, (start.x, start.y));
+                    assert_eq!(path.and_then(|path| path.last().copied()), Some((start.x, start.y)),
+                        "{} players, seed {}: can't walk from {:?} to ({}, {})", players, seed, home, start.x, start.y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn settings_shape_the_map() {
+        let settings = MapGeneratorSettings {
+            players: 4,
+            width: 3072.0,
+            height: 3072.0,
+            terrain_density: 0.0,
+            resource_density: 2.0,
+            expansions_per_player: 1,
+        };
+        let map = generate_map(&settings, 8);
+        assert_eq!((map.width, map.height), (3072.0, 3072.0));
+        assert_eq!(map.start_locations.len(), 4);
+        // Eight nodes at each main base and five at each expansion
+        assert_eq!(map.resources.len(), 4 * (8 + 5));
+        assert!(map.terrain.iter().all(|row| row.chars().all(|c| c == '.')));
+    }
+}
@@ -3,9 +3,22 @@ pub mod modes;
 pub mod screens;
 pub mod types;
 pub mod commands;
+pub mod definitions;
 pub mod resources;
+pub mod projectiles;
 pub mod rendering;
+pub mod simulation;
+pub mod rng;
+pub mod navigation;
+pub mod flow_field;
+pub mod spatial;
+pub mod terrain;
+pub mod map;
+pub mod map_generator;
+pub mod visibility;
+pub mod view;
 
 // Re-export commonly used types
 pub use state::GameState;
+pub use simulation::Simulation;
 pub use modes::{GameMode, GameScreen};
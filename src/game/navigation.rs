@@ -0,0 +1,419 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use crate::entity::Unit;
+use crate::game::terrain::TerrainMap;
+
+/// Side length of one navigation cell in world pixels.
+pub const NAV_CELL_SIZE: f32 = 32.0;
+
+// Integer move costs keep A* exact and deterministic
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Walkability grid over the whole map, used for unit pathfinding.
+///
+/// Built from impassable terrain and placed buildings, and rebuilt by the
+/// simulation whenever a building is placed or destroyed.
+#[derive(Debug, Clone)]
+pub struct NavGrid {
+    pub cell_size: f32,
+    pub width: usize,
+    pub height: usize,
+    blocked: Vec<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OpenNode {
+    f_cost: u32,
+    g_cost: u32,
+    index: usize,
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap: lowest f first, then deepest g, then index for stable ties
+        other.f_cost.cmp(&self.f_cost)
+            .then_with(|| self.g_cost.cmp(&other.g_cost))
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    pub fn new(map_width: f32, map_height: f32, cell_size: f32) -> Self {
+        let width = (map_width / cell_size).ceil().max(1.0) as usize;
+        let height = (map_height / cell_size).ceil().max(1.0) as usize;
+
+        Self {
+            cell_size,
+            width,
+            height,
+            blocked: vec![false; width * height],
+        }
+    }
+
+    /// Build the grid for a map, blocking impassable terrain and the
+    /// footprint of every building.
+    pub fn build(map_width: f32, map_height: f32, terrain: &TerrainMap, units: &[Unit]) -> Self {
+        let mut grid = Self::new(map_width, map_height, NAV_CELL_SIZE);
+
+        for cy in 0..grid.height {
+            for cx in 0..grid.width {
+                let (center_x, center_y) = grid.cell_center(cx, cy);
+                if !terrain.is_passable(center_x, center_y) {
+                    grid.set_blocked(cx, cy, true);
+                }
+            }
+        }
+
+        for unit in units {
+            let radius = unit.footprint_radius();
+            if radius > 0.0 {
+                grid.block_circle(unit.x, unit.y, radius);
+            }
+        }
+
+        grid
+    }
+
+    /// Mark every cell whose center lies inside the circle as impassable.
+    pub fn block_circle(&mut self, x: f32, y: f32, radius: f32) {
+        let (min_x, min_y) = self.world_to_cell(x - radius, y - radius);
+        let (max_x, max_y) = self.world_to_cell(x + radius, y + radius);
+
+        for cy in min_y..=max_y {
+            for cx in min_x..=max_x {
+                let (center_x, center_y) = self.cell_center(cx, cy);
+                if (center_x - x).powi(2) + (center_y - y).powi(2) <= radius * radius {
+                    self.set_blocked(cx, cy, true);
+                }
+            }
+        }
+    }
+
+    pub fn set_blocked(&mut self, cx: usize, cy: usize, blocked: bool) {
+        if cx < self.width && cy < self.height {
+            self.blocked[cy * self.width + cx] = blocked;
+        }
+    }
+
+    pub fn is_blocked(&self, cx: usize, cy: usize) -> bool {
+        cx >= self.width || cy >= self.height || self.blocked[cy * self.width + cx]
+    }
+
+    pub fn is_walkable(&self, x: f32, y: f32) -> bool {
+        let (cx, cy) = self.world_to_cell(x, y);
+        !self.is_blocked(cx, cy)
+    }
+
+    /// Cell containing a world position, clamped to the grid.
+    pub fn world_to_cell(&self, x: f32, y: f32) -> (usize, usize) {
+        let cx = (x / self.cell_size).floor().clamp(0.0, (self.width - 1) as f32) as usize;
+        let cy = (y / self.cell_size).floor().clamp(0.0, (self.height - 1) as f32) as usize;
+        (cx, cy)
+    }
+
+    pub fn cell_center(&self, cx: usize, cy: usize) -> (f32, f32) {
+        (
+            (cx as f32 + 0.5) * self.cell_size,
+            (cy as f32 + 0.5) * self.cell_size,
+        )
+    }
+
+    /// Closest walkable cell to the given one, searching outward ring by ring.
+    pub fn nearest_walkable(&self, cx: usize, cy: usize) -> Option<(usize, usize)> {
+        if !self.is_blocked(cx, cy) {
+            return Some((cx, cy));
+        }
+
+        let max_ring = self.width.max(self.height);
+        for ring in 1..max_ring as i32 {
+            let mut best: Option<((usize, usize), i32)> = None;
+
+            for dy in -ring..=ring {
+                for dx in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue;
+                    }
+                    let nx = cx as i32 + dx;
+                    let ny = cy as i32 + dy;
+                    if nx < 0 || ny < 0 || self.is_blocked(nx as usize, ny as usize) {
+                        continue;
+                    }
+                    let distance = dx * dx + dy * dy;
+                    if best.is_none_or(|(_, best_distance)| distance < best_distance) {
+                        best = Some(((nx as usize, ny as usize), distance));
+                    }
+                }
+            }
+
+            if let Some((cell, _)) = best {
+                return Some(cell);
+            }
+        }
+
+        None
+    }
+
+    /// Walkable neighbours of a cell with their move cost. Diagonals are only
+    /// allowed when both adjacent straight cells are open, so paths never
+    /// cut the corner of a building.
+    pub fn neighbors(&self, cx: usize, cy: usize) -> Vec<(usize, usize, u32)> {
+        let mut result = Vec::with_capacity(8);
+
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            let nx = cx as i32 + dx;
+            let ny = cy as i32 + dy;
+            if nx < 0 || ny < 0 || self.is_blocked(nx as usize, ny as usize) {
+                continue;
+            }
+
+            let diagonal = dx != 0 && dy != 0;
+            if diagonal &&
+               (self.is_blocked((cx as i32 + dx) as usize, cy) || self.is_blocked(cx, (cy as i32 + dy) as usize)) {
+                continue;
+            }
+
+            result.push((nx as usize, ny as usize, if diagonal { DIAGONAL_COST } else { STRAIGHT_COST }));
+        }
+
+        result
+    }
+
+    /// A* path between two world positions.
+    ///
+    /// Returns the smoothed list of waypoints, ending at `goal` or at the
+    /// closest reachable point to it when the goal itself is blocked or
+    /// walled off. Returns `None` when nothing reachable exists.
+    pub fn find_path(&self, start: (f32, f32), goal: (f32, f32)) -> Option<Vec<(f32, f32)>> {
+        let start_cell = self.world_to_cell(start.0, start.1);
+        let start_cell = self.nearest_walkable(start_cell.0, start_cell.1)?;
+        let goal_cell = self.world_to_cell(goal.0, goal.1);
+        let goal_blocked = self.is_blocked(goal_cell.0, goal_cell.1);
+        let goal_cell = self.nearest_walkable(goal_cell.0, goal_cell.1)?;
+
+        let final_point = if goal_blocked {
+            self.cell_center(goal_cell.0, goal_cell.1)
+        } else {
+            goal
+        };
+
+        // Straight line is always preferred when nothing is in the way
+        if self.line_of_sight(start, final_point) {
+            return Some(vec![final_point]);
+        }
+
+        let cells = self.astar(start_cell, goal_cell);
+
+        // Settle for the closest cell we could get to
+        let reached = cells.last().copied().unwrap_or(start_cell);
+        let final_point = if reached == goal_cell {
+            final_point
+        } else {
+            self.cell_center(reached.0, reached.1)
+        };
+
+        let mut waypoints: Vec<(f32, f32)> = cells.iter()
+            .skip(1)
+            .map(|&(cx, cy)| self.cell_center(cx, cy))
+            .collect();
+        waypoints.pop();
+        waypoints.push(final_point);
+
+        Some(self.smooth_path(start, &waypoints))
+    }
+
+    // Cells from start to goal, or to the reachable cell closest to the goal
+    // when it can't be reached
+    fn astar(&self, start: (usize, usize), goal: (usize, usize)) -> Vec<(usize, usize)> {
+        let cell_count = self.width * self.height;
+        let start_index = start.1 * self.width + start.0;
+        let goal_index = goal.1 * self.width + goal.0;
+
+        let mut g_costs = vec![u32::MAX; cell_count];
+        let mut came_from = vec![usize::MAX; cell_count];
+        let mut closed = vec![false; cell_count];
+        let mut open = BinaryHeap::new();
+
+        g_costs[start_index] = 0;
+        let mut closest = (self.heuristic(start, goal), start_index);
+        open.push(OpenNode {
+            f_cost: self.heuristic(start, goal),
+            g_cost: 0,
+            index: start_index,
+        });
+
+        while let Some(current) = open.pop() {
+            if current.index == goal_index {
+                closest = (0, goal_index);
+                break;
+            }
+
+            if closed[current.index] {
+                continue;
+            }
+            closed[current.index] = true;
+
+            let cx = current.index % self.width;
+            let cy = current.index / self.width;
+            closest = closest.min((self.heuristic((cx, cy), goal), current.index));
+
+            for (nx, ny, cost) in self.neighbors(cx, cy) {
+                let next_index = ny * self.width + nx;
+                let tentative = current.g_cost + cost;
+
+                if tentative < g_costs[next_index] {
+                    g_costs[next_index] = tentative;
+                    came_from[next_index] = current.index;
+                    open.push(OpenNode {
+                        f_cost: tentative + self.heuristic((nx, ny), goal),
+                        g_cost: tentative,
+                        index: next_index,
+                    });
+                }
+            }
+        }
+
+        // Walk back to the start
+        let mut index = closest.1;
+        let mut path = vec![(index % self.width, index / self.width)];
+        while index != start_index {
+            index = came_from[index];
+            path.push((index % self.width, index / self.width));
+        }
+        path.reverse();
+        path
+    }
+
+    // Octile distance, admissible for 8-way movement
+    fn heuristic(&self, a: (usize, usize), b: (usize, usize)) -> u32 {
+        let dx = (a.0 as i32 - b.0 as i32).unsigned_abs();
+        let dy = (a.1 as i32 - b.1 as i32).unsigned_abs();
+        STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+    }
+
+    /// Whether a straight walk between two points stays on walkable cells.
+    pub fn line_of_sight(&self, from: (f32, f32), to: (f32, f32)) -> bool {
+        let dx = to.0 - from.0;
+        let dy = to.1 - from.1;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let step = self.cell_size * 0.25;
+        let samples = (distance / step).ceil() as usize;
+
+        for i in 1..=samples {
+            let t = i as f32 / samples as f32;
+            if !self.is_walkable(from.0 + dx * t, from.1 + dy * t) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // String pulling: skip every waypoint that can be bypassed in a straight line
+    fn smooth_path(&self, start: (f32, f32), waypoints: &[(f32, f32)]) -> Vec<(f32, f32)> {
+        let mut smoothed = Vec::new();
+        let mut anchor = start;
+        let mut i = 0;
+
+        while i < waypoints.len() {
+            let mut furthest = i;
+            for j in (i + 1..waypoints.len()).rev() {
+                if self.line_of_sight(anchor, waypoints[j]) {
+                    furthest = j;
+                    break;
+                }
+            }
+
+            smoothed.push(waypoints[furthest]);
+            anchor = waypoints[furthest];
+            i = furthest + 1;
+        }
+
+        smoothed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 20x20 cells with a wall down column 10, open only at row 15
+    fn walled_grid() -> NavGrid {
+        let mut grid = NavGrid::new(640.0, 640.0, NAV_CELL_SIZE);
+        for cy in 0..grid.height {
+            if cy != 15 {
+                grid.set_blocked(10, cy, true);
+            }
+        }
+        grid
+    }
+
+    // Every leg of a path, from the start, stays on walkable cells
+    fn assert_walkable(grid: &NavGrid, start: (f32, f32), path: &[(f32, f32)]) {
+        let mut from = start;
+        for &point in path {
+            assert!(grid.line_of_sight(from, point), "leg {:?} -> {:?} crosses a blocked cell", from, point);
+            from = point;
+        }
+    }
+
+    #[test]
+    fn open_ground_is_one_straight_leg() {
+        let grid = NavGrid::new(640.0, 640.0, NAV_CELL_SIZE);
+        assert_eq!(grid.find_path((20.0, 20.0), (600.0, 500.0)), Some(vec![(600.0, 500.0)]));
+    }
+
+    #[test]
+    fn path_goes_through_the_gap() {
+        let grid = walled_grid();
+        let start = grid.cell_center(2, 2);
+        let goal = grid.cell_center(17, 2);
+
+        let path = grid.find_path(start, goal).expect("goal is reachable");
+        assert_eq!(path.last(), Some(&goal));
+        assert_walkable(&grid, start, &path);
+        // Smoothed down to the corners either side of the gap
+        assert!(path.len() <= 3, "path wasn't smoothed: {:?}", path);
+    }
+
+    #[test]
+    fn blocked_goal_ends_at_nearest_open_cell() {
+        let grid = walled_grid();
+        let start = grid.cell_center(2, 2);
+        let goal = grid.cell_center(10, 5);
+
+        let path = grid.find_path(start, goal).expect("a neighbour of the goal is reachable");
+        assert_eq!(path.last(), Some(&grid.cell_center(9, 5)));
+        assert_walkable(&grid, start, &path);
+    }
+
+    #[test]
+    fn walled_off_goal_ends_at_closest_reachable_cell() {
+        // Close the gap so the far side can't be reached
+        let mut grid = walled_grid();
+        grid.set_blocked(10, 15, true);
+        let start = grid.cell_center(2, 2);
+        let goal = grid.cell_center(12, 2);
+
+        let path = grid.find_path(start, goal).expect("the near side is reachable");
+        assert_eq!(path.last(), Some(&grid.cell_center(9, 2)));
+        assert_walkable(&grid, start, &path);
+    }
+
+    #[test]
+    fn no_path_without_walkable_cells() {
+        let mut grid = NavGrid::new(64.0, 64.0, NAV_CELL_SIZE);
+        for cy in 0..grid.height {
+            for cx in 0..grid.width {
+                grid.set_blocked(cx, cy, true);
+            }
+        }
+        assert_eq!(grid.find_path((16.0, 16.0), (48.0, 48.0)), None);
+    }
+}
@@ -0,0 +1,38 @@
+use crate::entity::DamageType;
+
+/// A shot in flight from a ranged unit; it deals its damage when it lands.
+#[derive(Debug, Clone)]
+pub struct Projectile {
+    pub x: f32,
+    pub y: f32,
+    // Unit it's following, `None` for a shot that missed or lost its target
+    pub target_id: Option<u32>,
+    // Where it lands, kept up to date while it follows its target
+    pub target_x: f32,
+    pub target_y: f32,
+    // Pixels per second
+    pub speed: f32,
+    // Player who fired it; splash never hurts their own units
+    pub player_id: usize,
+    pub damage: f32,
+    pub damage_type: DamageType,
+    // Enemies this close to where it lands take a share of the damage too
+    pub splash_radius: f32,
+}
+
+impl Projectile {
+    /// Direction of travel in radians, for drawing.
+    pub fn heading(&self) -> f32 {
+        (self.target_y - self.y).atan2(self.target_x - self.x)
+    }
+}
+
+/// Where a projectile landed, shown for a moment by the renderer.
+#[derive(Debug, Clone)]
+pub struct Impact {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+    // Game time it landed
+    pub time: f32,
+}
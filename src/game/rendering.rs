@@ -3,25 +3,62 @@ use crate::game::state::GameState;
 use crate::resources::manager::ResourceManager;
 use crate::entity::UnitType;
 use crate::game::types::ResourceType;
+use crate::game::terrain::{TerrainMap, TerrainType};
+use crate::game::map::{NeutralObject, NeutralObjectKind};
+use crate::game::commands::Command;
+use crate::game::projectiles::{Impact, Projectile};
+use crate::game::simulation::{IMPACT_LINGER, PLACEMENT_GRID_SIZE};
+use crate::game::view::PlayerView;
+use crate::game::visibility::{RememberedBuilding, Visibility};
 
 pub fn draw_game(game_state: &GameState, resource_manager: &ResourceManager) {
     // Clear and set camera offset
     let camera_x = game_state.camera_x;
     let camera_y = game_state.camera_y;
+    // Only draw what the local player can see
+    let view = game_state.simulation.view(game_state.current_player_id);
     
-    // Draw background pattern
-    draw_background(camera_x, camera_y);
+    // Draw the terrain under everything else
+    draw_background(&game_state.simulation.terrain, camera_x, camera_y, resource_manager);
     
     // Draw resource nodes
-    for node in &game_state.resource_nodes {
+    for (_, node) in view.resource_nodes() {
         draw_resource_node(node, camera_x, camera_y, resource_manager);
     }
     
+    // Draw neutral scenery
+    for object in &game_state.simulation.neutral_objects {
+        if view.is_explored(object.x, object.y) {
+            draw_neutral_object(object, camera_x, camera_y);
+        }
+    }
+    
+    // Draw enemy buildings where they were last seen
+    for building in view.known_enemy_buildings() {
+        if !view.is_visible(building.x, building.y) {
+            draw_remembered_building(building, camera_x, camera_y);
+        }
+    }
+    
     // Draw units
-    for unit in &game_state.units {
+    for unit in view.units() {
         draw_unit(unit, camera_x, camera_y, resource_manager, game_state);
     }
     
+    // Draw shots in flight and where they landed
+    for projectile in view.projectiles() {
+        draw_projectile(projectile, camera_x, camera_y, resource_manager);
+    }
+    for impact in view.impacts() {
+        draw_impact(impact, view.game_time(), camera_x, camera_y, resource_manager);
+    }
+    
+    // Black out unexplored ground and dim what isn't in sight
+    draw_fog(&view, camera_x, camera_y);
+    
+    // Show where the building being placed would go
+    draw_placement_ghost(game_state, camera_x, camera_y);
+    
     // Draw selection boxes
     draw_selection_indicators(game_state, camera_x, camera_y);
     
@@ -32,9 +69,9 @@ pub fn draw_game(game_state: &GameState, resource_manager: &ResourceManager) {
     draw_minimap(game_state);
 }
 
-fn draw_background(camera_x: f32, camera_y: f32) {
-    // Draw a simple tiled background
-    let tile_size = 128.0;
+fn draw_background(terrain: &TerrainMap, camera_x: f32, camera_y: f32, resource_manager: &ResourceManager) {
+    // Draw only the tiles in view
+    let tile_size = terrain.tile_size;
     let start_x = (camera_x / tile_size).floor() as i32;
     let start_y = (camera_y / tile_size).floor() as i32;
     let end_x = start_x + (screen_width() / tile_size).ceil() as i32 + 2;
@@ -44,20 +81,66 @@ fn draw_background(camera_x: f32, camera_y: f32) {
         for y in start_y..end_y {
             let world_x = x as f32 * tile_size - camera_x;
             let world_y = y as f32 * tile_size - camera_y;
+            let checker = (x + y) % 2 == 0;
             
-            // Alternate between two shades of green for a grass effect
-            let color = if (x + y) % 2 == 0 {
-                Color::new(0.2, 0.6, 0.2, 1.0)
+            let terrain_type = if x < 0 || y < 0 {
+                None
             } else {
-                Color::new(0.15, 0.5, 0.15, 1.0)
+                terrain.get(x as usize, y as usize)
             };
             
-            draw_rectangle(world_x, world_y, tile_size, tile_size, color);
+            match terrain_type {
+                Some(terrain_type) => {
+                    // Obstacles stand on grass
+                    if matches!(terrain_type, TerrainType::RockObstacle | TerrainType::Trees) {
+                        draw_terrain_tile(TerrainType::Grass, world_x, world_y, tile_size, checker, resource_manager);
+                    }
+                    draw_terrain_tile(terrain_type, world_x, world_y, tile_size, checker, resource_manager);
+                },
+                // Outside the map
+                None => draw_rectangle(world_x, world_y, tile_size, tile_size, Color::new(0.05, 0.05, 0.08, 1.0)),
+            }
         }
     }
 }
 
-fn draw_resource_node(node: &crate::game::resources::ResourceNode, camera_x: f32, camera_y: f32, _resource_manager: &ResourceManager) {
+fn draw_terrain_tile(terrain_type: TerrainType, x: f32, y: f32, size: f32, checker: bool, resource_manager: &ResourceManager) {
+    if let Some(texture) = resource_manager.get_texture(terrain_type.texture_name()) {
+        draw_texture_ex(*texture, x, y, WHITE, DrawTextureParams {
+            dest_size: Some(Vec2::new(size, size)),
+            ..Default::default()
+        });
+        return;
+    }
+    
+    // No texture on disk: fall back to flat colors, alternating shades like a checkerboard
+    let shade = if checker { 1.0 } else { 0.85 };
+    match terrain_type {
+        TerrainType::RockObstacle => {
+            draw_circle(x + size * 0.5, y + size * 0.5, size * 0.45, Color::new(0.4 * shade, 0.4 * shade, 0.42 * shade, 1.0));
+        },
+        TerrainType::Trees => {
+            draw_circle(x + size * 0.35, y + size * 0.4, size * 0.3, Color::new(0.05, 0.3 * shade, 0.08, 1.0));
+            draw_circle(x + size * 0.65, y + size * 0.6, size * 0.3, Color::new(0.05, 0.35 * shade, 0.1, 1.0));
+        },
+        _ => draw_rectangle(x, y, size, size, terrain_color(terrain_type, shade)),
+    }
+}
+
+fn terrain_color(terrain_type: TerrainType, shade: f32) -> Color {
+    let (r, g, b) = match terrain_type {
+        TerrainType::Grass => (0.2, 0.6, 0.2),
+        TerrainType::Dirt => (0.45, 0.33, 0.2),
+        TerrainType::Sand => (0.8, 0.72, 0.45),
+        TerrainType::Rock => (0.45, 0.42, 0.38),
+        TerrainType::Water => (0.15, 0.3, 0.65),
+        TerrainType::RockObstacle => (0.4, 0.4, 0.42),
+        TerrainType::Trees => (0.05, 0.3, 0.08),
+    };
+    Color::new(r * shade, g * shade, b * shade, 1.0)
+}
+
+fn draw_resource_node(node: &crate::game::resources::ResourceNode, camera_x: f32, camera_y: f32, resource_manager: &ResourceManager) {
     let screen_x = node.x - camera_x;
     let screen_y = node.y - camera_y;
     
@@ -65,13 +148,20 @@ fn draw_resource_node(node: &crate::game::resources::ResourceNode, camera_x: f32
     if screen_x > -50.0 && screen_x < screen_width() + 50.0 && 
        screen_y > -50.0 && screen_y < screen_height() + 50.0 {
         
-        let color = match node.resource_type {
-            ResourceType::Minerals => BLUE,
-            ResourceType::Energy => YELLOW,
-        };
-        
-        // Draw resource node
-        draw_circle(screen_x, screen_y, node.radius, color);
+        if let Some(texture) = resource_manager.get_texture(node.texture_name()) {
+            draw_texture_ex(*texture, screen_x - node.radius, screen_y - node.radius, WHITE, DrawTextureParams {
+                dest_size: Some(Vec2::new(node.radius * 2.0, node.radius * 2.0)),
+                ..Default::default()
+            });
+        } else {
+            // No texture on disk: a flat circle, faded once the node is dry
+            let color = match node.resource_type {
+                ResourceType::Minerals => BLUE,
+                ResourceType::Energy => YELLOW,
+            };
+            let color = if node.depleted_at.is_some() { Color::new(color.r * 0.4, color.g * 0.4, color.b * 0.4, 0.6) } else { color };
+            draw_circle(screen_x, screen_y, node.radius, color);
+        }
         
         // Draw resource amount text
         if node.resources > 0 {
@@ -81,6 +171,27 @@ fn draw_resource_node(node: &crate::game::resources::ResourceNode, camera_x: f32
     }
 }
 
+fn draw_neutral_object(object: &NeutralObject, camera_x: f32, camera_y: f32) {
+    let screen_x = object.x - camera_x;
+    let screen_y = object.y - camera_y;
+    
+    // Only draw if on screen
+    if screen_x > -object.radius && screen_x < screen_width() + object.radius &&
+       screen_y > -object.radius && screen_y < screen_height() + object.radius {
+        match object.kind {
+            NeutralObjectKind::Boulder => {
+                draw_circle(screen_x, screen_y, object.radius, Color::new(0.35, 0.33, 0.3, 1.0));
+                draw_circle_lines(screen_x, screen_y, object.radius, 2.0, Color::new(0.2, 0.19, 0.17, 1.0));
+            },
+            NeutralObjectKind::Ruins => {
+                let size = object.radius * 1.6;
+                draw_rectangle(screen_x - size / 2.0, screen_y - size / 2.0, size, size, Color::new(0.5, 0.45, 0.35, 1.0));
+                draw_rectangle_lines(screen_x - size / 2.0, screen_y - size / 2.0, size, size, 3.0, Color::new(0.3, 0.26, 0.2, 1.0));
+            },
+        }
+    }
+}
+
 fn draw_unit(unit: &crate::entity::Unit, camera_x: f32, camera_y: f32, _resource_manager: &ResourceManager, game_state: &GameState) {
     let screen_x = unit.x - camera_x;
     let screen_y = unit.y - camera_y;
@@ -96,23 +207,25 @@ fn draw_unit(unit: &crate::entity::Unit, camera_x: f32, camera_y: f32, _resource
             RED
         };
         
-        let size = match unit.unit_type {
-            UnitType::Worker => 15.0,
-            UnitType::Fighter => 18.0,
-            UnitType::Ranger => 16.0,
-            UnitType::Tank => 25.0,
-            UnitType::Building => 40.0,
-            UnitType::Headquarters => 50.0,
-        };
+        let size = unit.collision_radius();
         
-        // Draw unit
-        draw_circle(screen_x, screen_y, size, base_color);
+        // Draw unit; foundations are see-through scaffolding with a progress bar
+        if let Some(progress) = unit.construction_progress {
+            draw_circle(screen_x, screen_y, size, Color::new(base_color.r, base_color.g, base_color.b, 0.35));
+            draw_circle_lines(screen_x, screen_y, size, 2.0, base_color);
+            
+            let bar_width = size * 2.0;
+            draw_rectangle(screen_x - bar_width/2.0, screen_y + size + 6.0, bar_width, 5.0, DARKGRAY);
+            draw_rectangle(screen_x - bar_width/2.0, screen_y + size + 6.0, bar_width * progress, 5.0, ORANGE);
+        } else {
+            draw_circle(screen_x, screen_y, size, base_color);
+        }
         
         // Draw health bar if unit is damaged
         if unit.health < unit.max_health {
             let bar_width = size * 2.0;
             let bar_height = 4.0;
-            let health_ratio = unit.health as f32 / unit.max_health as f32;
+            let health_ratio = unit.health / unit.max_health;
             
             // Background
             draw_rectangle(screen_x - bar_width/2.0, screen_y - size - 10.0, bar_width, bar_height, RED);
@@ -124,7 +237,12 @@ fn draw_unit(unit: &crate::entity::Unit, camera_x: f32, camera_y: f32, _resource
         if unit.unit_type == UnitType::Worker {
             if let Some(resources) = unit.current_resources {
                 if resources > 0 {
-                    draw_circle(screen_x + 8.0, screen_y - 8.0, 4.0, GOLD);
+                    // Colored like the node it came from
+                    let color = match unit.carried_resource {
+                        Some(ResourceType::Energy) => YELLOW,
+                        _ => BLUE,
+                    };
+                    draw_circle(screen_x + 8.0, screen_y - 8.0, 4.0, color);
                     let text = format!("{}", resources);
                     draw_text(&text, screen_x + 15.0, screen_y - 5.0, 12.0, WHITE);
                 }
@@ -133,24 +251,226 @@ fn draw_unit(unit: &crate::entity::Unit, camera_x: f32, camera_y: f32, _resource
     }
 }
 
+// A beam pointing the way the shot flies; shells with splash are thicker
+fn draw_projectile(projectile: &Projectile, camera_x: f32, camera_y: f32, resource_manager: &ResourceManager) {
+    let screen_x = projectile.x - camera_x;
+    let screen_y = projectile.y - camera_y;
+    let (width, length) = if projectile.splash_radius > 0.0 { (8.0, 16.0) } else { (4.0, 24.0) };
+    let heading = projectile.heading();
+    
+    if let Some(texture) = resource_manager.get_texture("laser_beam") {
+        // The texture points up, so turn it a quarter further
+        draw_texture_ex(*texture, screen_x - width / 2.0, screen_y - length / 2.0, WHITE, DrawTextureParams {
+            dest_size: Some(Vec2::new(width, length)),
+            rotation: heading + std::f32::consts::FRAC_PI_2,
+            ..Default::default()
+        });
+    } else {
+        let (dx, dy) = (heading.cos() * length / 2.0, heading.sin() * length / 2.0);
+        draw_line(screen_x - dx, screen_y - dy, screen_x + dx, screen_y + dy, width / 2.0, ORANGE);
+    }
+}
+
+// A flash where a projectile landed, fading out; as wide as its splash
+fn draw_impact(impact: &Impact, game_time: f32, camera_x: f32, camera_y: f32, resource_manager: &ResourceManager) {
+    let screen_x = impact.x - camera_x;
+    let screen_y = impact.y - camera_y;
+    let radius = impact.radius.max(10.0);
+    let alpha = (1.0 - (game_time - impact.time) / IMPACT_LINGER).clamp(0.0, 1.0);
+    
+    if let Some(texture) = resource_manager.get_texture("impact_hit") {
+        draw_texture_ex(*texture, screen_x - radius, screen_y - radius, Color::new(1.0, 1.0, 1.0, alpha), DrawTextureParams {
+            dest_size: Some(Vec2::new(radius * 2.0, radius * 2.0)),
+            ..Default::default()
+        });
+    } else {
+        draw_circle(screen_x, screen_y, radius, Color::new(1.0, 0.6, 0.1, alpha * 0.6));
+    }
+}
+
+// Translucent footprint of the building being placed, red where it won't fit
+fn draw_placement_ghost(game_state: &GameState, camera_x: f32, camera_y: f32) {
+    let (building_type, x, y, valid) = match game_state.build_placement() {
+        Some(placement) => placement,
+        None => return,
+    };
+    let screen_x = x - camera_x;
+    let screen_y = y - camera_y;
+    let color = if valid { GREEN } else { RED };
+    let definition = game_state.simulation.definitions.building(&building_type);
+    let radius = definition.footprint;
+    
+    // Grid points around the cursor, to show what it snaps to
+    let grid = PLACEMENT_GRID_SIZE;
+    for gx in -3..=3 {
+        for gy in -3..=3 {
+            draw_circle(screen_x + gx as f32 * grid, screen_y + gy as f32 * grid, 1.5, Color::new(1.0, 1.0, 1.0, 0.3));
+        }
+    }
+    
+    draw_circle(screen_x, screen_y, radius, Color::new(color.r, color.g, color.b, 0.35));
+    draw_circle_lines(screen_x, screen_y, radius, 2.0, color);
+    
+    let label = format!("{:?} ({})", building_type, definition.cost);
+    let text_size = measure_text(&label, None, 16, 1.0);
+    draw_text(&label, screen_x - text_size.width / 2.0, screen_y - radius - 8.0, 16.0, WHITE);
+}
+
+fn draw_remembered_building(building: &RememberedBuilding, camera_x: f32, camera_y: f32) {
+    let screen_x = building.x - camera_x;
+    let screen_y = building.y - camera_y;
+    
+    // Only draw if on screen
+    if screen_x > -50.0 && screen_x < screen_width() + 50.0 && 
+       screen_y > -50.0 && screen_y < screen_height() + 50.0 {
+        let size = building.footprint_radius();
+        
+        // Outline only: we don't know what happened to it since
+        draw_circle(screen_x, screen_y, size, Color::new(0.6, 0.1, 0.1, 0.5));
+        draw_circle_lines(screen_x, screen_y, size, 2.0, RED);
+    }
+}
+
+fn fog_color(visibility: Visibility) -> Option<Color> {
+    match visibility {
+        Visibility::Unexplored => Some(BLACK),
+        Visibility::Explored => Some(Color::new(0.0, 0.0, 0.0, 0.5)),
+        Visibility::Visible => None,
+    }
+}
+
+fn draw_fog(view: &PlayerView, camera_x: f32, camera_y: f32) {
+    let grid = match view.visibility_grid() {
+        Some(grid) => grid,
+        None => return,
+    };
+    
+    // Draw only the cells in view
+    let cell_size = grid.cell_size;
+    let start_x = (camera_x / cell_size).floor().max(0.0) as usize;
+    let start_y = (camera_y / cell_size).floor().max(0.0) as usize;
+    let end_x = (((camera_x + screen_width()) / cell_size).ceil().max(0.0) as usize).min(grid.width);
+    let end_y = (((camera_y + screen_height()) / cell_size).ceil().max(0.0) as usize).min(grid.height);
+    
+    for cy in start_y..end_y {
+        for cx in start_x..end_x {
+            if let Some(color) = fog_color(grid.get(cx, cy)) {
+                let x = cx as f32 * cell_size - camera_x;
+                let y = cy as f32 * cell_size - camera_y;
+                draw_rectangle(x, y, cell_size, cell_size, color);
+            }
+        }
+    }
+}
+
+/// Cover the minimap where the player hasn't explored or can't see,
+/// one terrain tile at a time to keep it cheap on big maps.
+pub fn draw_minimap_fog(view: &PlayerView, minimap: &Rect) {
+    let terrain = view.terrain();
+    let (map_width, map_height) = view.map_size();
+    let scale_x = minimap.w / map_width;
+    let scale_y = minimap.h / map_height;
+    
+    for ty in 0..terrain.height {
+        for tx in 0..terrain.width {
+            let center_x = (tx as f32 + 0.5) * terrain.tile_size;
+            let center_y = (ty as f32 + 0.5) * terrain.tile_size;
+            if let Some(color) = fog_color(view.visibility_at(center_x, center_y)) {
+                draw_rectangle(
+                    minimap.x + tx as f32 * terrain.tile_size * scale_x,
+                    minimap.y + ty as f32 * terrain.tile_size * scale_y,
+                    terrain.tile_size * scale_x,
+                    terrain.tile_size * scale_y,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+// Lines from a selected unit through each of its queued orders
+fn draw_order_queue(unit: &crate::entity::Unit, game_state: &GameState, camera_x: f32, camera_y: f32) {
+    let view = game_state.simulation.view(game_state.current_player_id);
+    let mut from = (unit.x, unit.y);
+    
+    for order in &unit.orders {
+        let (point, color) = match *order {
+            Command::Move { x, y } => ((x, y), GREEN),
+            Command::AttackMove { x, y } => ((x, y), RED),
+            Command::Patrol { x, y } => ((x, y), YELLOW),
+            Command::Build { x, y, .. } => ((x, y), ORANGE),
+            Command::Attack { target_id } => {
+                match view.units().find(|u| u.id == target_id) {
+                    Some(target) => ((target.x, target.y), RED),
+                    None => continue,
+                }
+            },
+            Command::Gather { resource_id } => {
+                match game_state.simulation.resource_node(resource_id) {
+                    Some(node) => ((node.x, node.y), GOLD),
+                    None => continue,
+                }
+            },
+            Command::Guard { target_id } => {
+                match view.own_units().find(|u| u.id == target_id) {
+                    Some(guarded) => ((guarded.x, guarded.y), SKYBLUE),
+                    None => continue,
+                }
+            },
+            Command::Construct { target_id } => {
+                match view.own_units().find(|u| u.id == target_id) {
+                    Some(building) => ((building.x, building.y), ORANGE),
+                    None => continue,
+                }
+            },
+            Command::Train { .. } | Command::CancelTraining { .. } | Command::SetRallyPoint { .. }
+            | Command::Research { .. } | Command::CancelResearch
+            | Command::Stop | Command::SetStance { .. } | Command::CancelConstruction => continue,
+        };
+        
+        draw_line(from.0 - camera_x, from.1 - camera_y, point.0 - camera_x, point.1 - camera_y, 1.5, color);
+        draw_circle(point.0 - camera_x, point.1 - camera_y, 4.0, color);
+        from = point;
+    }
+}
+
 fn draw_selection_indicators(game_state: &GameState, camera_x: f32, camera_y: f32) {
     // Draw selection circles for selected units
     for &unit_id in &game_state.selected_units {
-        if let Some(unit) = game_state.units.iter().find(|u| u.id == unit_id) {
+        if let Some(unit) = game_state.simulation.units.iter().find(|u| u.id == unit_id) {
             let screen_x = unit.x - camera_x;
             let screen_y = unit.y - camera_y;
             
-            let size = match unit.unit_type {
-                UnitType::Worker => 20.0,
-                UnitType::Fighter => 23.0,
-                UnitType::Ranger => 21.0,
-                UnitType::Tank => 30.0,
-                UnitType::Building => 45.0,
-                UnitType::Headquarters => 55.0,
-            };
+            let size = unit.collision_radius() + 5.0;
             
             // Draw selection circle
             draw_circle_lines(screen_x, screen_y, size, 2.0, GREEN);
+            
+            // How far an armed building reaches
+            if unit.speed <= 0.0 && unit.attack_damage > 0.0 {
+                draw_circle_lines(screen_x, screen_y, unit.attack_range, 1.5, Color::new(1.0, 0.3, 0.3, 0.6));
+            }
+            
+            draw_order_queue(unit, game_state, camera_x, camera_y);
+            
+            // Line out to where units trained here will go, with a flag on the end
+            let rally = unit.rally_point.as_ref().and_then(|rally_point| game_state.simulation.rally_position(rally_point));
+            if let Some((rally_x, rally_y)) = rally {
+                let (flag_x, flag_y) = (rally_x - camera_x, rally_y - camera_y);
+                draw_line(screen_x, screen_y, flag_x, flag_y, 1.5, Color::new(1.0, 1.0, 1.0, 0.6));
+                draw_line(flag_x, flag_y, flag_x, flag_y - 18.0, 2.0, WHITE);
+                draw_triangle(
+                    vec2(flag_x, flag_y - 18.0),
+                    vec2(flag_x + 12.0, flag_y - 14.0),
+                    vec2(flag_x, flag_y - 10.0),
+                    GREEN,
+                );
+            }
+            
+            // Mark the enemy this unit is locked on to
+            if let Some(target) = unit.attack_target.and_then(|id| game_state.simulation.units.iter().find(|u| u.id == id)) {
+                draw_circle_lines(target.x - camera_x, target.y - camera_y, target.collision_radius() + 5.0, 2.0, RED);
+            }
         }
     }
     
@@ -172,7 +492,7 @@ fn draw_selection_indicators(game_state: &GameState, camera_x: f32, camera_y: f3
 
 fn draw_ui_overlay(game_state: &GameState, _resource_manager: &ResourceManager) {
     // Draw resource counter
-    let player = &game_state.players[game_state.current_player_id as usize];
+    let player = &game_state.simulation.players[game_state.current_player_id];
     let resources_text = format!("Minerals: {} | Energy: {}", player.minerals, player.energy);
     draw_text(&resources_text, 10.0, 30.0, 24.0, WHITE);
     
@@ -190,11 +510,37 @@ fn draw_minimap(game_state: &GameState) {
     draw_rectangle(minimap.x, minimap.y, minimap.w, minimap.h, Color::new(0.0, 0.0, 0.0, 0.7));
     draw_rectangle_lines(minimap.x, minimap.y, minimap.w, minimap.h, 2.0, WHITE);
     
-    // Draw units on minimap
-    let scale_x = minimap.w / game_state.map_width;
-    let scale_y = minimap.h / game_state.map_height;
+    // World to minimap scale
+    let scale_x = minimap.w / game_state.simulation.map_width;
+    let scale_y = minimap.h / game_state.simulation.map_height;
     
-    for unit in &game_state.units {
+    // Draw everything but grass so water and obstacles stand out
+    let terrain = &game_state.simulation.terrain;
+    for ty in 0..terrain.height {
+        for tx in 0..terrain.width {
+            match terrain.get(tx, ty) {
+                Some(TerrainType::Grass) | None => {},
+                Some(terrain_type) => {
+                    let mut color = terrain_color(terrain_type, 1.0);
+                    color.a = 0.8;
+                    draw_rectangle(
+                        minimap.x + tx as f32 * terrain.tile_size * scale_x,
+                        minimap.y + ty as f32 * terrain.tile_size * scale_y,
+                        terrain.tile_size * scale_x,
+                        terrain.tile_size * scale_y,
+                        color,
+                    );
+                },
+            }
+        }
+    }
+    
+    // Hide what the player hasn't seen
+    let view = game_state.simulation.view(game_state.current_player_id);
+    draw_minimap_fog(&view, minimap);
+    
+    // Draw units on minimap
+    for unit in view.units() {
         let minimap_x = minimap.x + unit.x * scale_x;
         let minimap_y = minimap.y + unit.y * scale_y;
         
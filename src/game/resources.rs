@@ -2,9 +2,25 @@ use crate::game::types::ResourceType;
 
 #[derive(Debug, Clone)]
 pub struct ResourceNode {
+    // Stays the same for the whole match, unlike the node's place in the list
+    pub id: usize,
     pub x: f32,
     pub y: f32,
     pub resources: i32,
     pub resource_type: ResourceType,
     pub radius: f32,
+    // Game time the node ran dry; it's removed a while after
+    pub depleted_at: Option<f32>,
+}
+
+impl ResourceNode {
+    /// Name of the texture the node is drawn with, which changes once it's dry.
+    pub fn texture_name(&self) -> &'static str {
+        match (&self.resource_type, self.depleted_at.is_some()) {
+            (ResourceType::Minerals, false) => "minerals",
+            (ResourceType::Minerals, true) => "minerals_depleted",
+            (ResourceType::Energy, false) => "energy",
+            (ResourceType::Energy, true) => "energy_depleted",
+        }
+    }
 }
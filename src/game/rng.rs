@@ -0,0 +1,98 @@
+/// Seeded random number generator owned by a match.
+///
+/// The simulation must never use a global RNG: the same seed has to produce
+/// the same sequence on every machine so matches can be replayed.
+#[derive(Debug, Clone)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // SplitMix64 - small, fast and well distributed for game purposes
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Random value in `[low, high)`, mirroring `macroquad::rand::gen_range`.
+    pub fn gen_range<T: SampleRange>(&mut self, low: T, high: T) -> T {
+        T::sample(self, low, high)
+    }
+}
+
+pub trait SampleRange: Sized {
+    fn sample(rng: &mut SimRng, low: Self, high: Self) -> Self;
+}
+
+impl SampleRange for f32 {
+    fn sample(rng: &mut SimRng, low: f32, high: f32) -> f32 {
+        low + (high - low) * rng.next_f32()
+    }
+}
+
+impl SampleRange for i32 {
+    fn sample(rng: &mut SimRng, low: i32, high: i32) -> i32 {
+        if high <= low {
+            return low;
+        }
+        let span = (high as i64 - low as i64) as u64;
+        (low as i64 + (rng.next_u64() % span) as i64) as i32
+    }
+}
+
+impl SampleRange for u32 {
+    fn sample(rng: &mut SimRng, low: u32, high: u32) -> u32 {
+        if high <= low {
+            return low;
+        }
+        low + (rng.next_u64() % (high - low) as u64) as u32
+    }
+}
+
+impl SampleRange for usize {
+    fn sample(rng: &mut SimRng, low: usize, high: usize) -> usize {
+        if high <= low {
+            return low;
+        }
+        low + (rng.next_u64() % (high - low) as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = SimRng::new(99);
+        let mut b = SimRng::new(99);
+        let mut c = SimRng::new(100);
+        let first: Vec<u64> = (0..16).map(|_| a.next_u64()).collect();
+        assert_eq!(first, (0..16).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(first, (0..16).map(|_| c.next_u64()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ranges_stay_in_bounds() {
+        let mut rng = SimRng::new(5);
+        for _ in 0..1000 {
+            let x = rng.gen_range(-2.5, 4.0);
+            assert!((-2.5..4.0).contains(&x));
+            assert!((3..7).contains(&rng.gen_range(3i32, 7)));
+            assert!((10..12).contains(&rng.gen_range(10usize, 12)));
+        }
+        assert_eq!(rng.gen_range(5u32, 5), 5);
+    }
+}
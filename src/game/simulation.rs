@@ -0,0 +1,2601 @@
+use macroquad::prelude::{Color, BLUE, GREEN, ORANGE, PINK, PURPLE, RED, SKYBLUE, YELLOW};
+use crate::entity::{BuildingType, GatherPhase, Unit, Player, Stance, UnitType, UpgradeType};
+use crate::game::commands::{Command, PlayerCommand, RallyPoint};
+use crate::game::definitions::{Cost, Definitions, UpgradeDefinition};
+use crate::game::resources::ResourceNode;
+use crate::game::projectiles::{Impact, Projectile};
+use crate::game::flow_field::{formation_targets, FlowField, GROUP_MOVE_THRESHOLD};
+use crate::game::map::{MapData, NeutralObject};
+use crate::game::map_generator::{generate_map, MapGeneratorSettings};
+use crate::game::navigation::{NavGrid, NAV_CELL_SIZE};
+use crate::game::rng::SimRng;
+use crate::game::spatial::{SpatialHash, SPATIAL_CELL_SIZE};
+use crate::game::terrain::TerrainMap;
+use crate::game::types::ResourceType;
+use crate::game::view::PlayerView;
+use crate::game::visibility::{VisibilityGrid, VISIBILITY_CELL_SIZE};
+
+/// Most units a building can have waiting in its production queue.
+pub const PRODUCTION_QUEUE_LIMIT: usize = 5;
+
+/// Spacing of the grid buildings snap to when placed by hand.
+pub const PLACEMENT_GRID_SIZE: f32 = NAV_CELL_SIZE;
+
+/// Simulation steps per second, independent of the render frame rate.
+pub const TICK_RATE: u32 = 30;
+/// Seconds of game time covered by one simulation step.
+pub const TICK_DT: f32 = 1.0 / TICK_RATE as f32;
+
+// Fraction of an overlap resolved each tick; below one so crowds settle without jitter
+const SEPARATION_STIFFNESS: f32 = 0.8;
+
+// How far a defensive unit chases before giving up and walking back
+const DEFENSIVE_LEASH: f32 = 300.0;
+// Enemies this close to a guarded unit get attacked
+const GUARD_RADIUS: f32 = 250.0;
+// How far a guard lets the unit it guards get ahead before following
+const GUARD_FOLLOW_DISTANCE: f32 = 80.0;
+
+// How a unit fighting on its own got on this tick
+enum Skirmish {
+    /// Chasing or fighting an enemy.
+    Fighting,
+    /// Lost its target and should get back to what it was doing.
+    Disengaged,
+    /// Nothing to fight.
+    Clear,
+}
+
+// Share of its full health a freshly laid foundation starts with
+const FOUNDATION_HEALTH: f32 = 0.1;
+// Share of the cost given back when a building under construction is cancelled
+const CONSTRUCTION_REFUND: f32 = 0.75;
+
+/// Most workers that mine one resource node at once; any more move on to
+/// the next node of the same kind.
+pub const NODE_WORKER_LIMIT: usize = 3;
+// How far from a full or dry node workers look for another one
+const NODE_SEARCH_RADIUS: f32 = 400.0;
+// How close to the edge of a node a worker has to stand to mine it
+const GATHER_REACH: f32 = 10.0;
+// Seconds a dry node stays on the map before it's removed
+const DEPLETED_NODE_LINGER: f32 = 10.0;
+
+// Share of a shot's damage that splashes onto enemies near where it lands
+const SPLASH_DAMAGE_SHARE: f32 = 0.5;
+/// Seconds an impact stays on screen after a projectile lands.
+pub const IMPACT_LINGER: f32 = 0.3;
+
+// Player colors in start location order
+const PLAYER_COLORS: [Color; 8] = [BLUE, RED, GREEN, YELLOW, PURPLE, ORANGE, SKYBLUE, PINK];
+
+/// The authoritative game world.
+///
+/// Everything in here is advanced by `tick` and nothing else, and none of it
+/// touches the window, input or renderer, so matches can run headless.
+pub struct Simulation {
+    pub units: Vec<Unit>,
+    pub players: Vec<Player>,
+    pub resource_nodes: Vec<ResourceNode>,
+    pub neutral_objects: Vec<NeutralObject>,
+    pub projectiles: Vec<Projectile>,
+    // Where projectiles landed in the last moment, for the renderer
+    pub impacts: Vec<Impact>,
+    pub next_unit_id: u32,
+    pub game_time: f32,
+    pub tick_count: u64,
+    pub map_width: f32,
+    pub map_height: f32,
+    pub terrain: TerrainMap,
+    pub start_locations: Vec<(f32, f32)>,
+    pub seed: u64,
+    pub rng: SimRng,
+    pub nav_grid: NavGrid,
+    nav_dirty: bool,
+    pub flow_fields: Vec<FlowField>,
+    next_flow_field_id: u32,
+    // Proximity indexes over `units` and `resource_nodes`, rebuilt every tick
+    pub unit_index: SpatialHash,
+    pub resource_index: SpatialHash,
+    // What each player has explored and can see, indexed by player id
+    pub visibility: Vec<VisibilityGrid>,
+    // Stats, costs and producers of every unit and building type
+    pub definitions: Definitions,
+}
+
+impl Simulation {
+    /// The default skirmish: two players on a map generated from `seed`.
+    pub fn new(seed: u64, definitions: Definitions) -> Self {
+        Self::generated(&MapGeneratorSettings::default(), seed, definitions)
+    }
+
+    /// A skirmish on a map generated from `settings` and `seed`.
+    pub fn generated(settings: &MapGeneratorSettings, seed: u64, definitions: Definitions) -> Self {
+        let map = generate_map(settings, seed);
+        Self::from_map(&map, seed, definitions)
+    }
+
+    /// Set up a match with one player per start location of `map`; the
+    /// first player is the local human and the rest are AI.
+    ///
+    /// The map is expected to have passed `MapData::validate` and the
+    /// definitions `Definitions::validate`.
+    pub fn from_map(map: &MapData, seed: u64, definitions: Definitions) -> Self {
+        // Validation already parsed the terrain, so this can only fail on a map that skipped it
+        let terrain = map.terrain_map()
+            .unwrap_or_else(|e| panic!("map {} has broken terrain: {}", map.name, e));
+
+        let players: Vec<Player> = (0..map.start_locations.len())
+            .map(|id| Player {
+                id,
+                minerals: 500,
+                energy: 200,
+                upgrades: Vec::new(),
+                supply_used: 0,
+                supply_cap: 0,
+                color: PLAYER_COLORS[id % PLAYER_COLORS.len()],
+                is_ai: id != 0,
+            })
+            .collect();
+
+        // Headquarters first, then two workers each on the side facing the middle of the map
+        let mut units = Vec::new();
+        let mut next_unit_id = 1;
+        for (player_id, start) in map.start_locations.iter().enumerate() {
+            units.push(Unit::new(next_unit_id, UnitType::Headquarters, start.x, start.y, player_id as u8, definitions.unit(&UnitType::Headquarters)));
+            next_unit_id += 1;
+        }
+        for (player_id, start) in map.start_locations.iter().enumerate() {
+            let to_center_x = map.width / 2.0 - start.x;
+            let to_center_y = map.height / 2.0 - start.y;
+            let length = (to_center_x * to_center_x + to_center_y * to_center_y).sqrt().max(1.0);
+            let (dir_x, dir_y) = (to_center_x / length, to_center_y / length);
+
+            for side in [-1.0, 1.0] {
+                let x = start.x + dir_x * 80.0 - dir_y * 25.0 * side;
+                let y = start.y + dir_y * 80.0 + dir_x * 25.0 * side;
+                units.push(Unit::new(next_unit_id, UnitType::Worker, x, y, player_id as u8, definitions.unit(&UnitType::Worker)));
+                next_unit_id += 1;
+            }
+        }
+
+        let resource_nodes = map.resources.iter()
+            .enumerate()
+            .map(|(id, resource)| ResourceNode {
+                id,
+                x: resource.x,
+                y: resource.y,
+                resources: resource.amount,
+                resource_type: resource.resource_type.clone(),
+                radius: resource.radius,
+                depleted_at: None,
+            })
+            .collect();
+
+        let mut simulation = Self {
+            units,
+            players,
+            resource_nodes,
+            neutral_objects: map.neutral_objects.clone(),
+            projectiles: Vec::new(),
+            impacts: Vec::new(),
+            next_unit_id,
+            game_time: 0.0,
+            tick_count: 0,
+            map_width: map.width,
+            map_height: map.height,
+            terrain,
+            start_locations: map.start_locations.iter().map(|start| (start.x, start.y)).collect(),
+            seed,
+            rng: SimRng::new(seed),
+            nav_grid: NavGrid::new(map.width, map.height, NAV_CELL_SIZE),
+            nav_dirty: false,
+            flow_fields: Vec::new(),
+            next_flow_field_id: 0,
+            unit_index: SpatialHash::new(map.width, map.height, SPATIAL_CELL_SIZE),
+            resource_index: SpatialHash::new(map.width, map.height, SPATIAL_CELL_SIZE),
+            visibility: (0..map.start_locations.len())
+                .map(|_| VisibilityGrid::new(map.width, map.height, VISIBILITY_CELL_SIZE))
+                .collect(),
+            definitions,
+        };
+        simulation.rebuild_navigation();
+        simulation.rebuild_spatial_index();
+        simulation.update_supply();
+        simulation.update_visibility();
+        simulation
+    }
+
+    /// Advance the world by `dt` seconds after applying `commands`.
+    pub fn tick(&mut self, dt: f32, commands: &[PlayerCommand]) {
+        for command in commands {
+            self.apply_command(command);
+        }
+
+        // Buildings were placed or destroyed since the last tick
+        if self.nav_dirty {
+            self.rebuild_navigation();
+        }
+
+        // Dry resource nodes disappear a while after running out
+        let game_time = self.game_time;
+        self.resource_nodes.retain(|node| node.depleted_at.is_none_or(|depleted_at| game_time - depleted_at < DEPLETED_NODE_LINGER));
+
+        // Commands and the AI may have added units since the last rebuild
+        self.rebuild_spatial_index();
+
+        self.game_time += dt;
+        self.tick_count += 1;
+
+        // Work through each unit's order queue
+        self.update_orders();
+
+        // Foundations with a worker beside them go up
+        self.update_construction(dt);
+
+        // Buildings train the front of their production queues
+        self.update_production(dt);
+
+        // Workers at a node mine it
+        self.update_gathering(dt);
+
+        // Research Centers work on their upgrades
+        self.update_research(dt);
+
+        // Process unit movement and actions
+        self.update_units(dt);
+
+        // Enhanced unit AI behavior
+        self.update_autonomous_behavior();
+
+        // Drop flow fields nobody is following any more
+        let units = &self.units;
+        self.flow_fields.retain(|field| units.iter().any(|u| u.flow_field == Some(field.id)));
+
+        self.update_supply();
+        self.update_visibility();
+    }
+
+    // Recount every player's supply: units alive or queued against what their
+    // finished buildings provide, up to the hard maximum
+    fn update_supply(&mut self) {
+        for player in &mut self.players {
+            player.supply_used = 0;
+            player.supply_cap = 0;
+        }
+        for unit in &self.units {
+            let player = match self.players.get_mut(unit.player_id) {
+                Some(player) if unit.health > 0.0 => player,
+                _ => continue,
+            };
+            player.supply_used += std::iter::once(&unit.unit_type)
+                .chain(&unit.production_queue)
+                .map(|unit_type| self.definitions.supply(unit_type))
+                .sum::<u32>();
+            if unit.is_complete() {
+                player.supply_cap += self.definitions.for_unit(&unit.unit_type, unit.building_type.as_ref()).supply_provided;
+            }
+        }
+        for player in &mut self.players {
+            player.supply_cap = player.supply_cap.min(self.definitions.max_supply);
+        }
+    }
+
+    /// Whether a player has the supply free to train another unit type.
+    pub fn has_supply(&self, player_id: usize, unit_type: &UnitType) -> bool {
+        self.players.get(player_id)
+            .is_some_and(|player| player.supply_used + self.definitions.supply(unit_type) <= player.supply_cap)
+    }
+
+    /// The world as `player_id` is allowed to see it.
+    pub fn view(&self, player_id: usize) -> PlayerView<'_> {
+        PlayerView::new(self, player_id)
+    }
+
+    // Recompute every player's fog of war from where their units stand now
+    fn update_visibility(&mut self) {
+        for (player_id, grid) in self.visibility.iter_mut().enumerate() {
+            grid.update(player_id, &self.units);
+        }
+    }
+
+    pub fn apply_command(&mut self, player_command: &PlayerCommand) {
+        let player_id = player_command.player_id;
+
+        match &player_command.command {
+            Command::Train { unit_type } => {
+                // Spread the work over the selected buildings that can train it
+                if !self.has_prerequisites(player_id, &self.definitions.unit(unit_type).prerequisites) {
+                    return;
+                }
+                let producer = player_command.unit_ids.iter()
+                    .filter_map(|id| self.units.iter().position(|u| u.id == *id && u.player_id == player_id))
+                    .filter(|&idx| self.definitions.can_train(&self.units[idx], unit_type) && self.units[idx].production_queue.len() < PRODUCTION_QUEUE_LIMIT)
+                    .min_by_key(|&idx| self.units[idx].production_queue.len());
+
+                // Queued units take up supply straight away
+                if let Some(idx) = producer {
+                    if self.can_afford(player_id, unit_type) && self.has_supply(player_id, unit_type) {
+                        self.deduct_cost(player_id, unit_type);
+                        self.units[idx].production_queue.push_back(unit_type.clone());
+                        self.players[player_id].supply_used += self.definitions.supply(unit_type);
+                    }
+                }
+            },
+            Command::CancelTraining { slot } => {
+                for &unit_id in &player_command.unit_ids {
+                    let producer = self.units.iter_mut().find(|u| u.id == unit_id && u.player_id == player_id);
+                    let cancelled = match producer {
+                        Some(producer) if *slot < producer.production_queue.len() => {
+                            // Whatever was spent training the front is lost with it
+                            if *slot == 0 {
+                                producer.production_progress = 0.0;
+                            }
+                            producer.production_queue.remove(*slot)
+                        },
+                        _ => None,
+                    };
+                    if let Some(unit_type) = cancelled {
+                        self.refund_cost(player_id, &unit_type);
+                    }
+                }
+            },
+            Command::SetRallyPoint { rally_point } => {
+                for &unit_id in &player_command.unit_ids {
+                    if let Some(building) = self.units.iter_mut().find(|u| u.id == unit_id && u.player_id == player_id) {
+                        if self.definitions.is_production_building(building) {
+                            building.rally_point = Some(rally_point.clone());
+                        }
+                    }
+                }
+            },
+            Command::Research { upgrade } => {
+                if !self.can_research(player_id, upgrade) {
+                    return;
+                }
+                let center = player_command.unit_ids.iter()
+                    .filter_map(|id| self.units.iter().position(|u| u.id == *id && u.player_id == player_id))
+                    .find(|&idx| {
+                        let unit = &self.units[idx];
+                        unit.building_type == Some(BuildingType::ResearchCenter) && unit.is_complete() && unit.research.is_none()
+                    });
+
+                if let Some(idx) = center {
+                    let cost = self.definitions.upgrade(upgrade).cost;
+                    let player = &mut self.players[player_id];
+                    player.minerals -= cost.minerals;
+                    player.energy -= cost.energy;
+                    self.units[idx].research = Some(upgrade.clone());
+                    self.units[idx].research_progress = 0.0;
+                }
+            },
+            Command::CancelResearch => {
+                for &unit_id in &player_command.unit_ids {
+                    let cancelled = self.units.iter_mut()
+                        .find(|u| u.id == unit_id && u.player_id == player_id)
+                        .and_then(|center| center.research.take());
+                    if let (Some(upgrade), Some(player)) = (cancelled, self.players.get_mut(player_id)) {
+                        let cost = self.definitions.upgrade(&upgrade).cost;
+                        player.minerals += cost.minerals;
+                        player.energy += cost.energy;
+                    }
+                }
+            },
+            Command::Stop => {
+                for &unit_id in &player_command.unit_ids {
+                    if let Some(unit) = self.units.iter_mut().find(|u| u.id == unit_id && u.player_id == player_id) {
+                        unit.clear_orders();
+                    }
+                }
+            },
+            Command::SetStance { stance } => {
+                for &unit_id in &player_command.unit_ids {
+                    if let Some(unit) = self.units.iter_mut().find(|u| u.id == unit_id && u.player_id == player_id) {
+                        unit.stance = *stance;
+                        unit.idle_position = None;
+                    }
+                }
+            },
+            Command::CancelConstruction => {
+                for &unit_id in &player_command.unit_ids {
+                    let building = self.units.iter_mut()
+                        .find(|u| u.id == unit_id && u.player_id == player_id && !u.is_complete() && u.health > 0.0);
+                    if let Some(building) = building {
+                        // Torn down with the dead at the end of the tick
+                        building.health = 0.0;
+                        let cost = building.building_type.as_ref()
+                            .map_or(Cost::default(), |building_type| self.definitions.building(building_type).cost);
+                        if let Some(player) = self.players.get_mut(player_id) {
+                            player.minerals += (cost.minerals as f32 * CONSTRUCTION_REFUND) as i32;
+                            player.energy += (cost.energy as f32 * CONSTRUCTION_REFUND) as i32;
+                        }
+                    }
+                }
+            },
+            order => {
+                // Only workers gather and build
+                let workers_only = matches!(order, Command::Gather { .. } | Command::Build { .. } | Command::Construct { .. });
+                let units: Vec<usize> = player_command.unit_ids.iter()
+                    .filter_map(|id| self.units.iter().position(|u| u.id == *id && u.player_id == player_id))
+                    .filter(|&idx| self.units[idx].speed > 0.0)
+                    .filter(|&idx| !workers_only || self.units[idx].unit_type == UnitType::Worker)
+                    // Nobody guards themselves
+                    .filter(|&idx| *order != Command::Guard { target_id: self.units[idx].id })
+                    .collect();
+
+                for &idx in &units {
+                    let unit = &mut self.units[idx];
+                    if !player_command.queued {
+                        unit.clear_orders();
+                    }
+                    unit.orders.push_back(order.clone());
+                }
+
+                // A large group moving right away shares one flow field
+                if let Command::Move { x, y } = order {
+                    if !player_command.queued && units.len() >= GROUP_MOVE_THRESHOLD {
+                        self.group_move(&units, *x, *y);
+                        for &idx in &units {
+                            let unit = &mut self.units[idx];
+                            unit.order_origin = Some((unit.x, unit.y));
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    /// Start, follow up on and finish the order at the front of every
+    /// unit's queue.
+    fn update_orders(&mut self) {
+        for idx in 0..self.units.len() {
+            // A finished order hands over to the next one in the same tick
+            for _ in 0..=self.units[idx].orders.len() {
+                let order = match self.units[idx].orders.front() {
+                    Some(order) => order.clone(),
+                    None => break,
+                };
+
+                let starting = self.units[idx].order_origin.is_none();
+                if starting {
+                    let unit = &mut self.units[idx];
+                    unit.order_origin = Some((unit.x, unit.y));
+                    unit.idle_position = None;
+                    unit.clear_target();
+                    // Anything but gathering ends the mining trip
+                    if !matches!(order, Command::Gather { .. }) {
+                        unit.gather_node = None;
+                    }
+                }
+
+                if !self.follow_order(idx, &order, starting) {
+                    break;
+                }
+
+                // Patrols go back to where the leg started and round again
+                let unit = &mut self.units[idx];
+                let origin = unit.order_origin.take();
+                unit.attack_target = None;
+                unit.orders.pop_front();
+                if let (Command::Patrol { .. }, Some((origin_x, origin_y))) = (&order, origin) {
+                    unit.orders.push_back(Command::Patrol { x: origin_x, y: origin_y });
+                }
+            }
+        }
+    }
+
+    // Chase a locked target until in range, then stand and fight; false once
+    // it's dead or out of the player's sight
+    fn engage(&mut self, idx: usize, target_id: u32) -> bool {
+        let player_id = self.units[idx].player_id;
+        let (enemy_x, enemy_y, enemy_footprint, distance) = match self.units.iter().find(|u| u.id == target_id) {
+            Some(enemy) if enemy.player_id != player_id && enemy.health > 0.0 => {
+                let unit = &self.units[idx];
+                (enemy.x, enemy.y, enemy.footprint_radius(), edge_distance(unit.x, unit.y, enemy))
+            },
+            _ => return false,
+        };
+        if !self.visibility.get(player_id).is_some_and(|grid| grid.is_visible(enemy_x, enemy_y)) {
+            return false;
+        }
+
+        let unit = &mut self.units[idx];
+        if distance < unit.attack_range {
+            if unit.target_x.is_some() {
+                unit.clear_target();
+            }
+            return true;
+        }
+
+        // Replan only once the target has moved away from where we were headed;
+        // paths to buildings end on the nearest open cell beside them
+        let tolerance = NAV_CELL_SIZE * 2.0 + enemy_footprint;
+        let heading_to_enemy = match (unit.target_x, unit.target_y) {
+            (Some(x), Some(y)) => ((x - enemy_x).powi(2) + (y - enemy_y).powi(2)).sqrt() < tolerance,
+            _ => false,
+        };
+        if !heading_to_enemy {
+            unit.target_x = Some(enemy_x);
+            unit.target_y = Some(enemy_y);
+        }
+        true
+    }
+
+    // Id of the closest enemy the unit's player can see within the unit's
+    // sight and within `leash` of `anchor`
+    fn nearest_visible_enemy(&self, idx: usize, anchor: (f32, f32), leash: f32) -> Option<u32> {
+        let unit = &self.units[idx];
+        let grid = self.visibility.get(unit.player_id)?;
+        self.unit_index
+            .nearest(unit.x, unit.y, unit.sight_radius(), |i| {
+                let other = self.units.get(i)?;
+                let near_anchor = ((other.x - anchor.0).powi(2) + (other.y - anchor.1).powi(2)).sqrt() <= leash;
+                (other.player_id != unit.player_id && other.health > 0.0 && near_anchor && grid.is_visible(other.x, other.y))
+                    .then(|| ((other.x - unit.x).powi(2) + (other.y - unit.y).powi(2)).sqrt())
+            })
+            .map(|i| self.units[i].id)
+    }
+
+    /// Fight enemies that come within `leash` of `anchor` without being
+    /// told to, for attack-moves, patrols, guards and idle stances.
+    fn skirmish(&mut self, idx: usize, anchor: (f32, f32), leash: f32) -> Skirmish {
+        let mut disengaged = false;
+
+        if let Some(target_id) = self.units[idx].attack_target {
+            let in_leash = self.units.iter()
+                .find(|u| u.id == target_id)
+                .is_some_and(|enemy| ((enemy.x - anchor.0).powi(2) + (enemy.y - anchor.1).powi(2)).sqrt() <= leash);
+            if in_leash && self.engage(idx, target_id) {
+                return Skirmish::Fighting;
+            }
+
+            let unit = &mut self.units[idx];
+            unit.attack_target = None;
+            unit.clear_target();
+            disengaged = true;
+        }
+
+        if let Some(enemy_id) = self.nearest_visible_enemy(idx, anchor, leash) {
+            self.units[idx].attack_target = Some(enemy_id);
+            self.engage(idx, enemy_id);
+            return Skirmish::Fighting;
+        }
+
+        if disengaged { Skirmish::Disengaged } else { Skirmish::Clear }
+    }
+
+    // Point a unit at what its order needs this tick; true once the order is done
+    fn follow_order(&mut self, idx: usize, order: &Command, starting: bool) -> bool {
+        match *order {
+            Command::Move { x, y } => {
+                if starting {
+                    self.units[idx].target_x = Some(x);
+                    self.units[idx].target_y = Some(y);
+                    return false;
+                }
+                // Arrived, or gave up on an unreachable spot
+                self.units[idx].target_x.is_none()
+            },
+            Command::Build { ref building_type, x, y } => {
+                let player_id = self.units[idx].player_id;
+
+                // Another worker got there first: help them instead
+                let foundation = self.units.iter()
+                    .find(|u| u.player_id == player_id && !u.is_complete() && u.health > 0.0
+                        && u.building_type.as_ref() == Some(building_type) && u.x == x && u.y == y)
+                    .map(|u| u.id);
+                if let Some(target_id) = foundation {
+                    self.start_constructing(idx, target_id);
+                    return false;
+                }
+
+                let unit = &self.units[idx];
+                let in_reach = ((x - unit.x).powi(2) + (y - unit.y).powi(2)).sqrt() <= build_reach(self.definitions.building(building_type).footprint, unit);
+                if in_reach {
+                    // Can't afford it any more, or the spot has been taken
+                    return match self.place_foundation(player_id, building_type, x, y) {
+                        Some(target_id) => {
+                            self.start_constructing(idx, target_id);
+                            false
+                        },
+                        None => true,
+                    };
+                }
+
+                if starting {
+                    self.units[idx].target_x = Some(x);
+                    self.units[idx].target_y = Some(y);
+                    return false;
+                }
+                // Gave up short of an unreachable spot
+                self.units[idx].target_x.is_none()
+            },
+            Command::Construct { target_id } => {
+                let player_id = self.units[idx].player_id;
+                let (building_x, building_y, building_radius) = match self.units.iter()
+                    .find(|u| u.id == target_id && u.player_id == player_id && !u.is_complete() && u.health > 0.0)
+                {
+                    Some(building) => (building.x, building.y, building.footprint_radius()),
+                    None => return true,
+                };
+
+                // Stand beside the foundation; `update_construction` does the work
+                let unit = &mut self.units[idx];
+                let distance = ((building_x - unit.x).powi(2) + (building_y - unit.y).powi(2)).sqrt();
+                if distance <= build_reach(building_radius, unit) {
+                    if unit.target_x.is_some() {
+                        unit.clear_target();
+                    }
+                } else if unit.target_x.is_none() {
+                    unit.target_x = Some(building_x);
+                    unit.target_y = Some(building_y);
+                }
+                false
+            },
+            Command::Attack { target_id } => {
+                if !self.engage(idx, target_id) {
+                    return true;
+                }
+                self.units[idx].attack_target = Some(target_id);
+                false
+            },
+            Command::AttackMove { x, y } | Command::Patrol { x, y } => {
+                // Take on any enemy that comes into sight along the way, then carry on to the point
+                let here = (self.units[idx].x, self.units[idx].y);
+                match self.skirmish(idx, here, f32::MAX) {
+                    Skirmish::Fighting => false,
+                    Skirmish::Disengaged => {
+                        self.units[idx].target_x = Some(x);
+                        self.units[idx].target_y = Some(y);
+                        false
+                    },
+                    Skirmish::Clear if starting => {
+                        self.units[idx].target_x = Some(x);
+                        self.units[idx].target_y = Some(y);
+                        false
+                    },
+                    Skirmish::Clear => self.units[idx].target_x.is_none(),
+                }
+            },
+            Command::Guard { target_id } => {
+                let player_id = self.units[idx].player_id;
+                let (guarded_x, guarded_y, guarded_radius) = match self.units.iter().find(|u| u.id == target_id && u.player_id == player_id) {
+                    Some(guarded) => (guarded.x, guarded.y, guarded.collision_radius()),
+                    None => return true,
+                };
+
+                if let Skirmish::Fighting = self.skirmish(idx, (guarded_x, guarded_y), GUARD_RADIUS) {
+                    return false;
+                }
+
+                // Keep up with the guarded unit, replanning only once it has moved on
+                let unit = &mut self.units[idx];
+                let distance = ((guarded_x - unit.x).powi(2) + (guarded_y - unit.y).powi(2)).sqrt();
+                if distance > GUARD_FOLLOW_DISTANCE + guarded_radius {
+                    let heading_to_guarded = match (unit.target_x, unit.target_y) {
+                        (Some(x), Some(y)) => ((x - guarded_x).powi(2) + (y - guarded_y).powi(2)).sqrt() < NAV_CELL_SIZE + guarded_radius,
+                        _ => false,
+                    };
+                    if !heading_to_guarded {
+                        unit.target_x = Some(guarded_x);
+                        unit.target_y = Some(guarded_y);
+                    }
+                } else if unit.target_x.is_some() {
+                    unit.clear_target();
+                }
+                false
+            },
+            Command::Gather { resource_id } => self.gather(idx, resource_id, starting),
+            Command::Train { .. } | Command::CancelTraining { .. } | Command::SetRallyPoint { .. }
+            | Command::Research { .. } | Command::CancelResearch
+            | Command::Stop | Command::SetStance { .. } | Command::CancelConstruction => true,
+        }
+    }
+
+    // Walk to a node, mine it (left to `update_gathering`), carry the load to
+    // the closest drop-off and go back for more; done once the node and every
+    // node of its kind nearby have run dry
+    fn gather(&mut self, idx: usize, resource_id: usize, starting: bool) -> bool {
+        if starting {
+            let unit = &mut self.units[idx];
+            unit.gather_node = Some(resource_id);
+            let full = unit.current_resources.unwrap_or(0) >= unit.resource_capacity.unwrap_or(0);
+            unit.gather_phase = Some(if full { GatherPhase::Returning } else { GatherPhase::ToNode });
+
+            // Too many workers on it already: spread out to the next node
+            let crowded = self.workers_on_node(resource_id, idx) >= NODE_WORKER_LIMIT;
+            if let (false, true, Some(next)) = (full, crowded, self.next_node(resource_id, idx, true)) {
+                self.retarget_gather(idx, next);
+                return false;
+            }
+        }
+
+        match self.units[idx].gather_phase {
+            Some(GatherPhase::Returning) => {
+                self.return_cargo(idx);
+                false
+            },
+            phase => {
+                let node = self.resource_node(resource_id)
+                    .filter(|node| node.depleted_at.is_none())
+                    .map(|node| (node.x, node.y, node.radius));
+                let (node_x, node_y, node_radius) = match node {
+                    Some(node) => node,
+                    None => {
+                        // Run dry: on to the closest node of the same kind nearby, or home with what it has
+                        let next = self.next_node(resource_id, idx, true).or_else(|| self.next_node(resource_id, idx, false));
+                        if let Some(next) = next {
+                            self.retarget_gather(idx, next);
+                            return false;
+                        }
+                        let unit = &mut self.units[idx];
+                        if unit.current_resources.unwrap_or(0) == 0 {
+                            return true;
+                        }
+                        unit.gather_phase = Some(GatherPhase::Returning);
+                        unit.clear_target();
+                        return false;
+                    },
+                };
+
+                // Mining itself is timed by `update_gathering`
+                let unit = &mut self.units[idx];
+                if let Some(GatherPhase::Mining(_)) = phase {
+                    return false;
+                }
+                let distance = ((node_x - unit.x).powi(2) + (node_y - unit.y).powi(2)).sqrt();
+                if distance < node_radius + unit.collision_radius() + GATHER_REACH {
+                    unit.clear_target();
+                    unit.gather_phase = Some(GatherPhase::Mining(0.0));
+                } else if unit.target_x.is_none() {
+                    unit.target_x = Some(node_x);
+                    unit.target_y = Some(node_y);
+                }
+                false
+            },
+        }
+    }
+
+    // Walk a worker's load to the closest drop-off and hand it in, then send
+    // the worker back to its node; waits where it is while there's no drop-off
+    fn return_cargo(&mut self, idx: usize) {
+        let unit = &self.units[idx];
+        let (player_id, unit_x, unit_y) = (unit.player_id, unit.x, unit.y);
+        let (drop_x, drop_y, deposit_range) = match self.find_drop_off(player_id, unit_x, unit_y) {
+            Some((drop_x, drop_y, footprint)) => (drop_x, drop_y, footprint + unit.collision_radius() + NAV_CELL_SIZE),
+            None => {
+                self.units[idx].clear_target();
+                return;
+            },
+        };
+
+        let unit = &mut self.units[idx];
+        if ((drop_x - unit_x).powi(2) + (drop_y - unit_y).powi(2)).sqrt() >= deposit_range {
+            // Head for whichever drop-off is closest now
+            if unit.target_x != Some(drop_x) || unit.target_y != Some(drop_y) {
+                unit.target_x = Some(drop_x);
+                unit.target_y = Some(drop_y);
+            }
+            return;
+        }
+
+        let amount = unit.current_resources.replace(0).unwrap_or(0) as i32;
+        let resource_type = unit.carried_resource.take();
+        unit.gather_phase = Some(GatherPhase::ToNode);
+        unit.clear_target();
+        if let Some(player) = self.players.get_mut(player_id) {
+            match resource_type {
+                Some(ResourceType::Energy) => player.energy += amount,
+                _ => player.minerals += amount,
+            }
+        }
+    }
+
+    // Point a worker's gather order at another node
+    fn retarget_gather(&mut self, idx: usize, resource_id: usize) {
+        let unit = &mut self.units[idx];
+        if let Some(order) = unit.orders.front_mut() {
+            *order = Command::Gather { resource_id };
+        }
+        unit.gather_node = Some(resource_id);
+        if unit.gather_phase != Some(GatherPhase::Returning) {
+            unit.gather_phase = Some(GatherPhase::ToNode);
+        }
+        unit.clear_target();
+    }
+
+    // Workers of the same player as `except`, other than it, with orders to
+    // gather from a node; enemy miners don't crowd anyone out
+    fn workers_on_node(&self, resource_id: usize, except: usize) -> usize {
+        let player_id = self.units[except].player_id;
+        self.units.iter()
+            .enumerate()
+            .filter(|&(i, unit)| i != except && unit.player_id == player_id)
+            .filter(|(_, unit)| unit.orders.front() == Some(&Command::Gather { resource_id }))
+            .count()
+    }
+
+    // The closest other node of the same kind near a node, only ones below the
+    // worker limit if `with_room`
+    fn next_node(&self, resource_id: usize, worker_idx: usize, with_room: bool) -> Option<usize> {
+        let worker = &self.units[worker_idx];
+        let (near_x, near_y, resource_type) = match self.resource_node(resource_id) {
+            Some(node) => (node.x, node.y, node.resource_type.clone()),
+            None => {
+                // Already removed: look around the worker for what it was carrying or mining
+                let resource_type = worker.carried_resource.clone()?;
+                (worker.x, worker.y, resource_type)
+            },
+        };
+        self.resource_nodes.iter()
+            .filter(|node| node.id != resource_id && node.resources > 0 && node.resource_type == resource_type)
+            .filter(|node| !with_room || self.workers_on_node(node.id, worker_idx) < NODE_WORKER_LIMIT)
+            .map(|node| (node.id, ((node.x - near_x).powi(2) + (node.y - near_y).powi(2)).sqrt()))
+            .filter(|&(_, distance)| distance <= NODE_SEARCH_RADIUS)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+
+    /// Mine for every worker standing at a node; once a worker has mined for
+    /// its gather time it takes a load and heads for a drop-off.
+    fn update_gathering(&mut self, dt: f32) {
+        for idx in 0..self.units.len() {
+            let worker = &self.units[idx];
+            let (elapsed, resource_id) = match (worker.gather_phase, worker.orders.front()) {
+                (Some(GatherPhase::Mining(elapsed)), Some(Command::Gather { resource_id })) if worker.health > 0.0 => (elapsed + dt, *resource_id),
+                _ => continue,
+            };
+            if elapsed < worker.gather_time {
+                self.units[idx].gather_phase = Some(GatherPhase::Mining(elapsed));
+                continue;
+            }
+            // Ran dry under it; the gather order moves it on next tick
+            let node = match self.resource_nodes.iter_mut().find(|node| node.id == resource_id && node.resources > 0) {
+                Some(node) => node,
+                None => continue,
+            };
+
+            // Mining another kind of resource drops what was carried
+            let worker = &mut self.units[idx];
+            if worker.carried_resource.as_ref().is_some_and(|carried| *carried != node.resource_type) {
+                worker.current_resources = Some(0);
+            }
+            let carried = worker.current_resources.unwrap_or(0);
+            let amount = worker.resource_capacity.unwrap_or(0).saturating_sub(carried).min(node.resources as u32);
+            node.resources -= amount as i32;
+            if node.resources <= 0 {
+                node.depleted_at = Some(self.game_time);
+            }
+            worker.current_resources = Some(carried + amount);
+            worker.carried_resource = Some(node.resource_type.clone());
+            worker.gather_phase = Some(GatherPhase::Returning);
+        }
+    }
+
+    // Swap the worker's build order for one to work on the foundation
+    fn start_constructing(&mut self, idx: usize, target_id: u32) {
+        let unit = &mut self.units[idx];
+        if let Some(order) = unit.orders.front_mut() {
+            *order = Command::Construct { target_id };
+        }
+        unit.order_origin = None;
+        unit.clear_target();
+    }
+
+    /// Advance every foundation that has a worker beside it, however many;
+    /// health grows with progress until the building is finished.
+    fn update_construction(&mut self, dt: f32) {
+        for idx in 0..self.units.len() {
+            let building = &self.units[idx];
+            let (progress, build_time) = match (building.construction_progress, &building.building_type) {
+                (Some(progress), Some(building_type)) if building.health > 0.0 => (progress, self.definitions.building(building_type).build_time),
+                _ => continue,
+            };
+            if !self.is_being_built(idx) {
+                continue;
+            }
+
+            let step = (dt / build_time).min(1.0 - progress);
+            let building = &mut self.units[idx];
+            building.health = (building.health + building.max_health * (1.0 - FOUNDATION_HEALTH) * step).min(building.max_health);
+            building.construction_progress = if progress + step >= 1.0 { None } else { Some(progress + step) };
+        }
+    }
+
+    /// Advance every building's production queue and send out whatever
+    /// finishes training.
+    fn update_production(&mut self, dt: f32) {
+        for idx in 0..self.units.len() {
+            let producer = &mut self.units[idx];
+            let unit_type = match producer.production_queue.front() {
+                Some(unit_type) if producer.health > 0.0 => unit_type.clone(),
+                _ => continue,
+            };
+
+            producer.production_progress += dt;
+            if producer.production_progress < self.definitions.unit(&unit_type).build_time {
+                continue;
+            }
+            producer.production_queue.pop_front();
+            producer.production_progress = 0.0;
+
+            // New units walk out of the front of the building, then on to the rally point
+            let (x, y) = (producer.x, producer.y + producer.footprint_radius() + 20.0);
+            let player_id = producer.player_id;
+            let rally_point = producer.rally_point.clone();
+            let order = rally_point.and_then(|rally_point| self.rally_order(&rally_point, &unit_type));
+            self.spawn_unit(unit_type, x, y, player_id);
+            if let (Some(order), Some(unit)) = (order, self.units.last_mut()) {
+                unit.orders.push_back(order);
+            }
+        }
+    }
+
+    // The first order of a unit trained at a building with this rally point
+    fn rally_order(&self, rally_point: &RallyPoint, unit_type: &UnitType) -> Option<Command> {
+        match *rally_point {
+            RallyPoint::Position { x, y } => Some(Command::Move { x, y }),
+            RallyPoint::Unit { target_id } => Some(Command::Guard { target_id }),
+            RallyPoint::Resource { resource_id } if *unit_type == UnitType::Worker => Some(Command::Gather { resource_id }),
+            RallyPoint::Resource { resource_id } => self.resource_node(resource_id)
+                .map(|node| Command::Move { x: node.x, y: node.y }),
+        }
+    }
+
+    /// Where a rally point is right now; `None` once the unit or node it
+    /// points at is gone.
+    pub fn rally_position(&self, rally_point: &RallyPoint) -> Option<(f32, f32)> {
+        match *rally_point {
+            RallyPoint::Position { x, y } => Some((x, y)),
+            RallyPoint::Unit { target_id } => self.units.iter().find(|u| u.id == target_id).map(|u| (u.x, u.y)),
+            RallyPoint::Resource { resource_id } => self.resource_node(resource_id).map(|node| (node.x, node.y)),
+        }
+    }
+
+    // Whether a worker ordered to build this foundation is standing beside it
+    fn is_being_built(&self, building_idx: usize) -> bool {
+        let building = &self.units[building_idx];
+        let reach = building.footprint_radius() + self.unit_index.max_radius() + NAV_CELL_SIZE;
+        self.unit_index.query(building.x, building.y, reach).into_iter()
+            .filter_map(|i| self.units.get(i))
+            .any(|worker| {
+                let distance = ((building.x - worker.x).powi(2) + (building.y - worker.y).powi(2)).sqrt();
+                worker.player_id == building.player_id
+                    && worker.orders.front() == Some(&Command::Construct { target_id: building.id })
+                    && distance <= build_reach(building.footprint_radius(), worker)
+            })
+    }
+
+    /// Whether a footprint of `radius` at a spot lies inside the map and on
+    /// passable terrain.
+    pub fn footprint_on_open_ground(&self, x: f32, y: f32, radius: f32) -> bool {
+        if x < radius || y < radius || x > self.map_width - radius || y > self.map_height - radius {
+            return false;
+        }
+
+        // The center and eight points around the edge all have to be passable
+        self.terrain.is_passable(x, y) && (0..8).all(|i| {
+            let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+            self.terrain.is_passable(x + angle.cos() * radius, y + angle.sin() * radius)
+        })
+    }
+
+    /// Whether a building fits at a spot: on the map, on open ground and
+    /// clear of other buildings, resource nodes and neutral objects.
+    /// Units in the way get shoved aside.
+    pub fn can_place_building(&self, building_type: &BuildingType, x: f32, y: f32) -> bool {
+        let radius = self.definitions.building(building_type).footprint;
+        self.footprint_on_open_ground(x, y, radius)
+            && !self.units.iter().any(|u| u.footprint_radius() > 0.0 && u.health > 0.0 && overlaps_footprint(x, y, radius, u.x, u.y, u.footprint_radius()))
+            && !self.resource_nodes.iter().any(|node| overlaps_footprint(x, y, radius, node.x, node.y, node.radius))
+            && !self.neutral_objects.iter().any(|object| overlaps_footprint(x, y, radius, object.x, object.y, object.radius))
+    }
+
+    /// Whether a player has a finished building of every type in `prerequisites`.
+    pub fn has_prerequisites(&self, player_id: usize, prerequisites: &[BuildingType]) -> bool {
+        self.missing_prerequisites(player_id, prerequisites).is_empty()
+    }
+
+    /// The building types in `prerequisites` a player has no finished one of.
+    pub fn missing_prerequisites(&self, player_id: usize, prerequisites: &[BuildingType]) -> Vec<BuildingType> {
+        prerequisites.iter()
+            .filter(|&required| {
+                !self.units.iter().any(|u| {
+                    u.player_id == player_id && u.is_complete() && u.health > 0.0 && u.building_type.as_ref() == Some(required)
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Whether a player could start researching an upgrade right now: not
+    /// already done or under way, unlocked and affordable.
+    pub fn can_research(&self, player_id: usize, upgrade: &UpgradeType) -> bool {
+        let player = match self.players.get(player_id) {
+            Some(player) => player,
+            None => return false,
+        };
+        let definition = self.definitions.upgrade(upgrade);
+        !player.upgrades.contains(upgrade)
+            && !self.is_researching(player_id, upgrade)
+            && self.has_prerequisites(player_id, &definition.prerequisites)
+            && player.minerals >= definition.cost.minerals
+            && player.energy >= definition.cost.energy
+    }
+
+    /// Whether one of a player's buildings is researching an upgrade.
+    pub fn is_researching(&self, player_id: usize, upgrade: &UpgradeType) -> bool {
+        self.units.iter().any(|u| u.player_id == player_id && u.health > 0.0 && u.research.as_ref() == Some(upgrade))
+    }
+
+    /// Advance every Research Center's upgrade; a finished one improves the
+    /// player's existing units and every one trained after.
+    fn update_research(&mut self, dt: f32) {
+        for idx in 0..self.units.len() {
+            let center = &mut self.units[idx];
+            let upgrade = match &center.research {
+                Some(upgrade) if center.health > 0.0 => upgrade.clone(),
+                _ => continue,
+            };
+
+            center.research_progress += dt;
+            if center.research_progress < self.definitions.upgrade(&upgrade).research_time {
+                continue;
+            }
+            center.research = None;
+            center.research_progress = 0.0;
+
+            let player_id = center.player_id;
+            if let Some(player) = self.players.get_mut(player_id) {
+                player.upgrades.push(upgrade.clone());
+            }
+            let definition = self.definitions.upgrade(&upgrade);
+            for unit in self.units.iter_mut().filter(|u| u.player_id == player_id) {
+                apply_upgrade(unit, definition);
+            }
+        }
+    }
+
+    /// Pay for and lay the foundation of a building; `None` if the player
+    /// can't afford it, lacks its prerequisites or the spot isn't free.
+    pub fn place_foundation(&mut self, player_id: usize, building_type: &BuildingType, x: f32, y: f32) -> Option<u32> {
+        let definition = self.definitions.building(building_type);
+        let cost = definition.cost;
+        let player = self.players.get(player_id)?;
+        if player.minerals < cost.minerals || player.energy < cost.energy
+            || !self.has_prerequisites(player_id, &definition.prerequisites)
+            || !self.can_place_building(building_type, x, y) {
+            return None;
+        }
+        self.players[player_id].minerals -= cost.minerals;
+        self.players[player_id].energy -= cost.energy;
+
+        let id = self.spawn_building(building_type.clone(), x, y, player_id);
+        let foundation = self.units.last_mut()?;
+        foundation.construction_progress = Some(0.0);
+        foundation.health = foundation.max_health * FOUNDATION_HEALTH;
+        Some(id)
+    }
+
+    // What a unit without orders does about enemies, depending on its stance
+    fn follow_stance(&mut self, idx: usize) {
+        let unit = &mut self.units[idx];
+        // Walking somewhere it was sent directly, or back to its post
+        if unit.target_x.is_some() && unit.attack_target.is_none() {
+            return;
+        }
+        let here = (unit.x, unit.y);
+        let post = *unit.idle_position.get_or_insert(here);
+
+        match unit.stance {
+            Stance::Aggressive => {
+                // Stay wherever the chase ended
+                if let Skirmish::Disengaged = self.skirmish(idx, here, f32::MAX) {
+                    self.units[idx].idle_position = None;
+                }
+            },
+            Stance::Defensive => {
+                if let Skirmish::Disengaged = self.skirmish(idx, post, DEFENSIVE_LEASH) {
+                    self.units[idx].target_x = Some(post.0);
+                    self.units[idx].target_y = Some(post.1);
+                }
+            },
+            // Shooting whatever is in range is left to `fight`
+            Stance::HoldPosition | Stance::Passive => {},
+        }
+    }
+
+    fn update_units(&mut self, dt: f32) {
+        for unit_idx in 0..self.units.len() {
+            // Skip dead units
+            if self.units[unit_idx].health <= 0.0 {
+                continue;
+            }
+
+            // Update attack cooldown
+            if self.units[unit_idx].current_cooldown > 0.0 {
+                self.units[unit_idx].current_cooldown -= dt;
+            }
+
+            // Buildings stay put, and armed ones only fire once finished
+            let unit_type = self.units[unit_idx].unit_type.clone();
+            if unit_type == UnitType::Building || unit_type == UnitType::Headquarters {
+                if self.units[unit_idx].is_complete() {
+                    self.fight(unit_idx);
+                }
+                continue;
+            }
+
+            // Move unit toward target if it has one
+            if let (Some(target_x), Some(target_y)) = (self.units[unit_idx].target_x, self.units[unit_idx].target_y) {
+                self.move_unit(unit_idx, target_x, target_y, dt);
+            }
+
+            self.fight(unit_idx);
+        }
+
+        // Shots in flight land on what's left
+        self.update_projectiles(dt);
+
+        // Remove dead units, freeing the ground under destroyed buildings
+        if self.units.iter().any(|unit| unit.health <= 0.0 && unit.footprint_radius() > 0.0) {
+            self.nav_dirty = true;
+        }
+        self.units.retain(|unit| unit.health > 0.0);
+        self.rebuild_spatial_index();
+
+        // Keep units from stacking on top of each other
+        self.separate_units();
+    }
+
+    // Fire at the locked target if it's in range, otherwise at the closest enemy
+    // in range; static defenses go for enemies shooting at friendlies nearby first
+    fn fight(&mut self, unit_idx: usize) {
+        let unit = &self.units[unit_idx];
+        if unit.current_cooldown > 0.0 || unit.attack_damage <= 0.0 {
+            return;
+        }
+        let (unit_x, unit_y, player_id, attack_range, locked) = (unit.x, unit.y, unit.player_id, unit.attack_range, unit.attack_target);
+        let defends_allies = unit.speed <= 0.0;
+
+        let mut nearest_enemy_dist = f32::MAX;
+        let mut nearest_enemy_idx = None;
+        let mut nearest_attacker_dist = f32::MAX;
+        let mut nearest_attacker_idx = None;
+        let mut locked_idx = None;
+
+        // Padding the search so large buildings are caught by their edge
+        let candidates = self.unit_index.query(unit_x, unit_y, attack_range + self.unit_index.max_radius());
+        for i in candidates {
+            let other_unit = match self.units.get(i) {
+                Some(other_unit) => other_unit,
+                None => continue,
+            };
+            if other_unit.player_id == player_id || other_unit.health <= 0.0 {
+                continue;
+            }
+
+            let dist = edge_distance(unit_x, unit_y, other_unit);
+            if dist < attack_range {
+                if locked == Some(other_unit.id) {
+                    locked_idx = Some(i);
+                }
+                if dist < nearest_enemy_dist {
+                    nearest_enemy_dist = dist;
+                    nearest_enemy_idx = Some(i);
+                }
+                if defends_allies && dist < nearest_attacker_dist && self.is_attacking_near(other_unit, player_id, unit_x, unit_y) {
+                    nearest_attacker_dist = dist;
+                    nearest_attacker_idx = Some(i);
+                }
+            }
+        }
+
+        // A unit locked on to a target holds fire for anything else, and
+        // passive units only shoot what they're told to
+        let enemy_idx = match (locked, self.units[unit_idx].stance) {
+            (Some(_), _) => locked_idx,
+            (None, Stance::Passive) => None,
+            (None, _) => nearest_attacker_idx.or(nearest_enemy_idx),
+        };
+        match enemy_idx {
+            Some(enemy_idx) => {
+                self.units[unit_idx].current_cooldown = self.units[unit_idx].attack_cooldown;
+                self.attack(unit_idx, enemy_idx);
+            },
+            None => self.units[unit_idx].last_target = None,
+        }
+    }
+
+    // Whether `enemy` last fired at one of `player_id`'s units within
+    // `GUARD_RADIUS` of a point
+    fn is_attacking_near(&self, enemy: &Unit, player_id: usize, x: f32, y: f32) -> bool {
+        enemy.last_target
+            .and_then(|id| self.units.iter().find(|u| u.id == id))
+            .is_some_and(|victim| {
+                victim.player_id == player_id && ((victim.x - x).powi(2) + (victim.y - y).powi(2)).sqrt() < GUARD_RADIUS
+            })
+    }
+
+    // Hit at once up close, or fire a projectile that lands a moment later
+    fn attack(&mut self, unit_idx: usize, enemy_idx: usize) {
+        self.units[unit_idx].last_target = Some(self.units[enemy_idx].id);
+        let (attacker, target) = (&self.units[unit_idx], &self.units[enemy_idx]);
+        if attacker.projectile_speed <= 0.0 {
+            let damage = self.definitions.hit_damage(attacker.attack_damage, attacker.damage_type, target);
+            self.units[enemy_idx].health -= damage;
+            return;
+        }
+
+        // A target on the move dodges some shots, which land where it was
+        let missed = target.is_moving() && self.rng.next_f32() < attacker.moving_miss_chance;
+        let projectile = Projectile {
+            x: attacker.x,
+            y: attacker.y,
+            target_id: (!missed).then_some(target.id),
+            target_x: target.x,
+            target_y: target.y,
+            speed: attacker.projectile_speed,
+            player_id: attacker.player_id,
+            damage: attacker.attack_damage,
+            damage_type: attacker.damage_type,
+            splash_radius: attacker.splash_radius,
+        };
+        self.projectiles.push(projectile);
+    }
+
+    /// Move shots in flight and deal damage where they land.
+    fn update_projectiles(&mut self, dt: f32) {
+        let game_time = self.game_time;
+        self.impacts.retain(|impact| game_time - impact.time < IMPACT_LINGER);
+
+        let mut in_flight = Vec::with_capacity(self.projectiles.len());
+        for mut projectile in std::mem::take(&mut self.projectiles) {
+            // Follow the target while it lives, else carry on to where it was
+            if let Some(target_id) = projectile.target_id {
+                match self.units.iter().find(|u| u.id == target_id && u.health > 0.0) {
+                    Some(target) => (projectile.target_x, projectile.target_y) = (target.x, target.y),
+                    None => projectile.target_id = None,
+                }
+            }
+
+            let dx = projectile.target_x - projectile.x;
+            let dy = projectile.target_y - projectile.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            let step = projectile.speed * dt;
+            if distance > step {
+                projectile.x += dx / distance * step;
+                projectile.y += dy / distance * step;
+                in_flight.push(projectile);
+            } else {
+                projectile.x = projectile.target_x;
+                projectile.y = projectile.target_y;
+                self.land(&projectile);
+            }
+        }
+        self.projectiles = in_flight;
+    }
+
+    // Damage the target a projectile was following and splash enemies around it
+    fn land(&mut self, projectile: &Projectile) {
+        if let Some(target) = projectile.target_id.and_then(|id| self.units.iter_mut().find(|u| u.id == id)) {
+            target.health -= self.definitions.hit_damage(projectile.damage, projectile.damage_type, target);
+        }
+
+        if projectile.splash_radius > 0.0 {
+            let splash_damage = projectile.damage * SPLASH_DAMAGE_SHARE;
+            let candidates = self.unit_index.query(projectile.x, projectile.y, projectile.splash_radius + self.unit_index.max_radius());
+            for i in candidates {
+                let unit = match self.units.get_mut(i) {
+                    Some(unit) => unit,
+                    None => continue,
+                };
+                if unit.player_id == projectile.player_id || unit.health <= 0.0 || Some(unit.id) == projectile.target_id {
+                    continue;
+                }
+                if edge_distance(projectile.x, projectile.y, unit) < projectile.splash_radius {
+                    unit.health -= self.definitions.hit_damage(splash_damage, projectile.damage_type, unit);
+                }
+            }
+        }
+
+        self.impacts.push(Impact {
+            x: projectile.x,
+            y: projectile.y,
+            radius: projectile.splash_radius,
+            time: self.game_time,
+        });
+    }
+
+    /// Re-bucket units and resource nodes by position.
+    ///
+    /// Must run after anything removes or reorders units, since the indexes
+    /// hold positions in `units` and `resource_nodes`.
+    pub fn rebuild_spatial_index(&mut self) {
+        self.unit_index.rebuild(self.units.iter().map(|unit| (unit.x, unit.y, unit.collision_radius())));
+        self.resource_index.rebuild(self.resource_nodes.iter().map(|node| (node.x, node.y, node.radius)));
+    }
+
+    /// Push overlapping units apart.
+    ///
+    /// Moving units shoulder idle friendlies out of the way, buildings never
+    /// give way, and nobody is pushed onto ground the nav grid blocks.
+    fn separate_units(&mut self) {
+        let mut pushes = vec![(0.0f32, 0.0f32); self.units.len()];
+
+        for i in 0..self.units.len() {
+            let reach = self.units[i].collision_radius() + self.unit_index.max_radius();
+            let mut neighbours = self.unit_index.query(self.units[i].x, self.units[i].y, reach);
+            // Visit pairs in a fixed order so the result doesn't depend on the grid layout
+            neighbours.sort_unstable();
+
+            for j in neighbours {
+                if j <= i || j >= self.units.len() {
+                    continue;
+                }
+                let a = &self.units[i];
+                let b = &self.units[j];
+
+                let (weight_a, weight_b) = separation_weights(a, b);
+                if weight_a + weight_b <= 0.0 {
+                    continue;
+                }
+
+                let dx = b.x - a.x;
+                let dy = b.y - a.y;
+                let min_distance = a.collision_radius() + b.collision_radius();
+                let distance_sq = dx * dx + dy * dy;
+                if distance_sq >= min_distance * min_distance {
+                    continue;
+                }
+
+                let distance = distance_sq.sqrt();
+                let (normal_x, normal_y) = if distance > 0.001 {
+                    (dx / distance, dy / distance)
+                } else {
+                    // Exactly stacked: split along a direction derived from the ids so replays agree
+                    let angle = (a.id.wrapping_mul(31) ^ b.id) as f32 * 2.399_963;
+                    (angle.cos(), angle.sin())
+                };
+
+                let push = (min_distance - distance) * SEPARATION_STIFFNESS;
+                pushes[i].0 -= normal_x * push * weight_a;
+                pushes[i].1 -= normal_y * push * weight_a;
+                pushes[j].0 += normal_x * push * weight_b;
+                pushes[j].1 += normal_y * push * weight_b;
+            }
+        }
+
+        for (unit, (push_x, push_y)) in self.units.iter_mut().zip(pushes) {
+            if push_x == 0.0 && push_y == 0.0 {
+                continue;
+            }
+
+            // Resolve each axis on its own so units slide along walls instead of sticking
+            let stuck = !self.nav_grid.is_walkable(unit.x, unit.y);
+            if stuck || self.nav_grid.is_walkable(unit.x + push_x, unit.y) {
+                unit.x += push_x;
+            }
+            if stuck || self.nav_grid.is_walkable(unit.x, unit.y + push_y) {
+                unit.y += push_y;
+            }
+        }
+    }
+
+    // Whether a unit near its destination has bumped into a friendly that is
+    // idle there, or bound for the same spot and closer to it, so two units
+    // can't shove for one spot forever
+    fn crowded_at_destination(&self, unit_idx: usize, goal: (f32, f32)) -> bool {
+        let unit = &self.units[unit_idx];
+        let distance_to_goal = ((goal.0 - unit.x).powi(2) + (goal.1 - unit.y).powi(2)).sqrt();
+
+        let reach = unit.collision_radius() + self.unit_index.max_radius() + 1.0;
+        self.unit_index.query(unit.x, unit.y, reach).into_iter().any(|i| {
+            let other = match self.units.get(i) {
+                Some(other) => other,
+                None => return false,
+            };
+            if i == unit_idx || other.player_id != unit.player_id || other.speed <= 0.0 {
+                return false;
+            }
+
+            if other.target_x.is_some() {
+                let same_goal = other.path_goal == unit.path_goal;
+                let other_to_goal = ((goal.0 - other.x).powi(2) + (goal.1 - other.y).powi(2)).sqrt();
+                if !same_goal || other_to_goal >= distance_to_goal {
+                    return false;
+                }
+            }
+
+            let contact = unit.collision_radius() + other.collision_radius();
+            let distance = ((other.x - unit.x).powi(2) + (other.y - unit.y).powi(2)).sqrt();
+            distance <= contact + 1.0 && distance_to_goal <= contact * 3.0
+        })
+    }
+
+    // Advance a unit one tick along its flow field or planned path
+    fn move_unit(&mut self, unit_idx: usize, target_x: f32, target_y: f32, dt: f32) {
+        let unit_x = self.units[unit_idx].x;
+        let unit_y = self.units[unit_idx].y;
+
+        // Terrain speeds units up or slows them down; anyone caught on impassable ground may still walk off it
+        let terrain_modifier = match self.terrain.terrain_at(unit_x, unit_y) {
+            Some(terrain) if terrain.is_passable() => terrain.speed_modifier(),
+            _ => 1.0,
+        };
+        let step = self.units[unit_idx].speed * terrain_modifier * dt;
+
+        // Plan a new path whenever the target changed since the last plan
+        if self.units[unit_idx].path_goal != Some((target_x, target_y)) {
+            self.plan_path(unit_idx, target_x, target_y);
+        }
+
+        // Group orders follow the shared flow field until the unit nears the formation
+        if let Some(field_id) = self.units[unit_idx].flow_field {
+            let direction = self.flow_fields.iter()
+                .find(|field| field.id == field_id)
+                .filter(|field| {
+                    let distance_to_goal = ((field.goal.0 - unit_x).powi(2) + (field.goal.1 - unit_y).powi(2)).sqrt();
+                    distance_to_goal > field.arrival_radius
+                })
+                .and_then(|field| field.direction_at(unit_x, unit_y));
+
+            match direction {
+                Some((dir_x, dir_y)) => {
+                    self.units[unit_idx].x += dir_x * step;
+                    self.units[unit_idx].y += dir_y * step;
+                    return;
+                },
+                None => {
+                    // Close enough (or stranded): finish with an ordinary path to this unit's slot
+                    self.units[unit_idx].flow_field = None;
+                    if let (Some(goal_x), Some(goal_y)) = (self.units[unit_idx].target_x, self.units[unit_idx].target_y) {
+                        self.plan_path(unit_idx, goal_x, goal_y);
+                    }
+                },
+            }
+        }
+
+        // Head for the next waypoint
+        if let Some(&(waypoint_x, waypoint_y)) = self.units[unit_idx].path.first() {
+            let dx = waypoint_x - unit_x;
+            let dy = waypoint_y - unit_y;
+            let distance = (dx * dx + dy * dy).sqrt();
+
+            // Settle beside friends already occupying the destination instead of shoving forever
+            let last_waypoint = self.units[unit_idx].path.len() == 1;
+            if last_waypoint && distance > step && self.crowded_at_destination(unit_idx, (waypoint_x, waypoint_y)) {
+                self.units[unit_idx].clear_target();
+                return;
+            }
+
+            if distance < 5.0 || distance <= step {
+                // Waypoint reached, stop once the path is used up
+                if distance <= step {
+                    self.units[unit_idx].x = waypoint_x;
+                    self.units[unit_idx].y = waypoint_y;
+                }
+                self.units[unit_idx].path.remove(0);
+                if self.units[unit_idx].path.is_empty() {
+                    self.units[unit_idx].clear_target();
+                }
+            } else {
+                self.units[unit_idx].x += dx / distance * step;
+                self.units[unit_idx].y += dy / distance * step;
+            }
+        } else {
+            self.units[unit_idx].clear_target();
+        }
+    }
+
+    /// Send a large group to a point through one shared flow field, keeping
+    /// each unit's offset from the group center around the destination.
+    fn group_move(&mut self, unit_indices: &[usize], x: f32, y: f32) {
+        let positions: Vec<(f32, f32)> = unit_indices.iter()
+            .map(|&idx| (self.units[idx].x, self.units[idx].y))
+            .collect();
+        let targets = formation_targets(&positions, (x, y));
+
+        let field_id = self.next_flow_field_id;
+        self.next_flow_field_id += 1;
+        let mut field = FlowField::build(field_id, &self.nav_grid, (x, y));
+
+        // Hand over to per-unit paths once inside the formation footprint
+        field.arrival_radius = targets.iter()
+            .map(|t| ((t.0 - x).powi(2) + (t.1 - y).powi(2)).sqrt())
+            .fold(0.0, f32::max) + self.nav_grid.cell_size * 2.0;
+        self.flow_fields.push(field);
+
+        for (&idx, &(target_x, target_y)) in unit_indices.iter().zip(targets.iter()) {
+            // Slots that land on a building move to the nearest open cell
+            let (target_x, target_y) = if self.nav_grid.is_walkable(target_x, target_y) {
+                (target_x, target_y)
+            } else {
+                let cell = self.nav_grid.world_to_cell(target_x, target_y);
+                match self.nav_grid.nearest_walkable(cell.0, cell.1) {
+                    Some((cx, cy)) => self.nav_grid.cell_center(cx, cy),
+                    None => (x, y),
+                }
+            };
+
+            let unit = &mut self.units[idx];
+            unit.target_x = Some(target_x);
+            unit.target_y = Some(target_y);
+            unit.path = vec![(target_x, target_y)];
+            unit.path_goal = Some((target_x, target_y));
+            unit.flow_field = Some(field_id);
+        }
+    }
+
+    /// Plan a path for a unit and adopt it, or drop the order if unreachable.
+    fn plan_path(&mut self, unit_idx: usize, target_x: f32, target_y: f32) {
+        let start = (self.units[unit_idx].x, self.units[unit_idx].y);
+
+        match self.nav_grid.find_path(start, (target_x, target_y)) {
+            Some(path) => {
+                // A blocked target is replaced by the closest reachable point
+                let (goal_x, goal_y) = path.last().copied().unwrap_or((target_x, target_y));
+                let unit = &mut self.units[unit_idx];
+                unit.target_x = Some(goal_x);
+                unit.target_y = Some(goal_y);
+                unit.path = path;
+                unit.path_goal = Some((goal_x, goal_y));
+                unit.flow_field = None;
+            },
+            None => self.units[unit_idx].clear_target(),
+        }
+    }
+
+    /// Rebuild the navigation grid and make every moving unit replan.
+    fn rebuild_navigation(&mut self) {
+        self.nav_grid = NavGrid::build(self.map_width, self.map_height, &self.terrain, &self.units);
+        for object in &self.neutral_objects {
+            self.nav_grid.block_circle(object.x, object.y, object.radius);
+        }
+        self.nav_dirty = false;
+
+        for field in &mut self.flow_fields {
+            field.rebuild(&self.nav_grid);
+        }
+
+        // Paths may now cross new buildings; flow field followers keep their field
+        for unit in &mut self.units {
+            if unit.flow_field.is_none() {
+                unit.path_goal = None;
+            }
+        }
+    }
+
+    // Autonomous unit behavior
+    fn update_autonomous_behavior(&mut self) {
+        // Make units more autonomous
+        for i in 0..self.units.len() {
+            let unit_type = self.units[i].unit_type.clone();
+            let unit_x = self.units[i].x;
+            let unit_y = self.units[i].y;
+
+            // Units with orders of their own are busy
+            if !self.units[i].orders.is_empty() {
+                continue;
+            }
+
+            // Auto-assign tasks based on unit type and situation
+            match unit_type {
+                // Workers whose last order was a gather go back to the node they were mining, or the
+                // closest one of the same kind once that runs dry; stopped or moved workers stay put
+                UnitType::Worker if self.units[i].target_x.is_none() && self.units[i].gather_node.is_some() => {
+                    let worker = &self.units[i];
+                    let last_node = worker.gather_node.and_then(|resource_id| self.resource_node(resource_id));
+                    let resource_type = worker.carried_resource.clone().or_else(|| last_node.map(|node| node.resource_type.clone()));
+                    let node = last_node
+                        .filter(|node| node.resources > 0)
+                        .map(|node| node.id)
+                        .or_else(|| self.find_nearest_resource(unit_x, unit_y, resource_type.as_ref()))
+                        .or_else(|| self.find_nearest_resource(unit_x, unit_y, None));
+                    if let Some(resource_id) = node {
+                        self.units[i].orders.push_back(Command::Gather { resource_id });
+                    }
+                },
+                // Deal with enemies the way the unit's stance says
+                UnitType::Fighter | UnitType::Ranger | UnitType::Tank => self.follow_stance(i),
+                _ => {}
+            }
+        }
+    }
+
+    // Helper method to find a player's headquarters
+    pub fn find_headquarters(&self, player_id: usize) -> Option<(f32, f32)> {
+        self.units.iter()
+            .find(|u| u.unit_type == UnitType::Headquarters && u.player_id == player_id)
+            .map(|u| (u.x, u.y))
+    }
+
+    /// Whether workers can drop resources off at a unit right now.
+    pub fn accepts_resources(&self, unit: &Unit) -> bool {
+        unit.is_complete() && unit.health > 0.0 && self.definitions.is_drop_off(unit)
+    }
+
+    /// Position and footprint radius of the closest of a player's buildings
+    /// that takes resources.
+    pub fn find_drop_off(&self, player_id: usize, x: f32, y: f32) -> Option<(f32, f32, f32)> {
+        self.units.iter()
+            .filter(|unit| unit.player_id == player_id && self.accepts_resources(unit))
+            .min_by(|a, b| {
+                let distance_a = (a.x - x).powi(2) + (a.y - y).powi(2);
+                let distance_b = (b.x - x).powi(2) + (b.y - y).powi(2);
+                distance_a.total_cmp(&distance_b)
+            })
+            .map(|unit| (unit.x, unit.y, unit.footprint_radius()))
+    }
+
+    /// A resource node by id, while it's still on the map.
+    pub fn resource_node(&self, resource_id: usize) -> Option<&ResourceNode> {
+        self.resource_nodes.iter().find(|node| node.id == resource_id)
+    }
+
+    /// Id of the closest resource node that still has resources, only of
+    /// `resource_type` if given.
+    pub fn find_nearest_resource(&self, x: f32, y: f32, resource_type: Option<&ResourceType>) -> Option<usize> {
+        self.resource_index
+            .nearest(x, y, f32::MAX, |i| {
+                let node = self.resource_nodes.get(i)?;
+                (node.resources > 0 && resource_type.is_none_or(|wanted| node.resource_type == *wanted))
+                    .then(|| ((node.x - x).powi(2) + (node.y - y).powi(2)).sqrt())
+            })
+            .map(|i| self.resource_nodes[i].id)
+    }
+
+    /// Position of the closest living enemy of `player_id` within `max_range`.
+    pub fn find_nearest_enemy(&self, x: f32, y: f32, player_id: usize, max_range: f32) -> Option<(f32, f32)> {
+        self.unit_index
+            .nearest(x, y, max_range, |i| {
+                let unit = self.units.get(i)?;
+                (unit.player_id != player_id && unit.health > 0.0)
+                    .then(|| ((unit.x - x).powi(2) + (unit.y - y).powi(2)).sqrt())
+            })
+            .map(|i| (self.units[i].x, self.units[i].y))
+    }
+
+    /// The player's own unit under a world position, if any.
+    pub fn unit_at(&self, x: f32, y: f32, player_id: usize) -> Option<u32> {
+        self.unit_index.query(x, y, 40.0).into_iter()
+            .filter_map(|i| self.units.get(i))
+            .filter(|unit| unit.player_id == player_id)
+            .find(|unit| {
+                let distance = ((unit.x - x).powi(2) + (unit.y - y).powi(2)).sqrt();
+                let selection_radius = match unit.unit_type {
+                    UnitType::Building | UnitType::Headquarters => 40.0,
+                    _ => 15.0
+                };
+                distance < selection_radius
+            })
+            .map(|unit| unit.id)
+    }
+
+    /// An enemy of `player_id` under a world position, if any.
+    pub fn enemy_at(&self, x: f32, y: f32, player_id: usize) -> Option<u32> {
+        self.unit_index.query(x, y, 20.0).into_iter()
+            .filter_map(|i| self.units.get(i))
+            .filter(|unit| unit.player_id != player_id)
+            .find(|unit| ((unit.x - x).powi(2) + (unit.y - y).powi(2)).sqrt() < 20.0)
+            .map(|unit| unit.id)
+    }
+
+    /// Id of the resource node under a world position, if any.
+    pub fn resource_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.resource_index.query(x, y, self.resource_index.max_radius()).into_iter()
+            .filter_map(|i| self.resource_nodes.get(i))
+            .find(|node| ((node.x - x).powi(2) + (node.y - y).powi(2)).sqrt() < node.radius)
+            .map(|node| node.id)
+    }
+
+    pub fn spawn_unit(&mut self, unit_type: UnitType, x: f32, y: f32, player_id: usize) -> u32 {
+        let definition = self.definitions.for_unit(&unit_type, None);
+        let unit = Unit::new(self.next_unit_id, unit_type, x, y, player_id as u8, definition);
+        self.add_unit(unit)
+    }
+
+    /// Spawn a finished building of `building_type`.
+    pub fn spawn_building(&mut self, building_type: BuildingType, x: f32, y: f32, player_id: usize) -> u32 {
+        let definition = self.definitions.building(&building_type);
+        let mut unit = Unit::new(self.next_unit_id, UnitType::Building, x, y, player_id as u8, definition);
+        unit.building_type = Some(building_type);
+        self.add_unit(unit)
+    }
+
+    fn add_unit(&mut self, mut unit: Unit) -> u32 {
+        let id = unit.id;
+        if let Some(player) = self.players.get(unit.player_id) {
+            for upgrade in &player.upgrades {
+                apply_upgrade(&mut unit, self.definitions.upgrade(upgrade));
+            }
+        }
+        self.next_unit_id += 1;
+
+        // New buildings change where units can walk
+        if unit.footprint_radius() > 0.0 {
+            self.nav_dirty = true;
+        }
+
+        self.units.push(unit);
+        id
+    }
+
+    pub fn can_afford(&self, player_id: usize, unit_type: &UnitType) -> bool {
+        let player = &self.players[player_id];
+        self.definitions.training_cost(unit_type)
+            .is_some_and(|cost| player.minerals >= cost.minerals && player.energy >= cost.energy)
+    }
+
+    pub fn deduct_cost(&mut self, player_id: usize, unit_type: &UnitType) {
+        if let (Some(player), Some(cost)) = (self.players.get_mut(player_id), self.definitions.training_cost(unit_type)) {
+            player.minerals -= cost.minerals;
+            player.energy -= cost.energy;
+        }
+    }
+
+    /// Give back everything `deduct_cost` took for a unit.
+    pub fn refund_cost(&mut self, player_id: usize, unit_type: &UnitType) {
+        if let (Some(player), Some(cost)) = (self.players.get_mut(player_id), self.definitions.training_cost(unit_type)) {
+            player.minerals += cost.minerals;
+            player.energy += cost.energy;
+        }
+    }
+}
+
+/// The placement grid point closest to a world position.
+pub fn snap_to_placement_grid(x: f32, y: f32) -> (f32, f32) {
+    ((x / PLACEMENT_GRID_SIZE).round() * PLACEMENT_GRID_SIZE, (y / PLACEMENT_GRID_SIZE).round() * PLACEMENT_GRID_SIZE)
+}
+
+/// Whether a building of footprint `radius` placed at `(x, y)` would
+/// overlap a circle.
+pub fn overlaps_footprint(x: f32, y: f32, radius: f32, other_x: f32, other_y: f32, other_radius: f32) -> bool {
+    ((other_x - x).powi(2) + (other_y - y).powi(2)).sqrt() < other_radius + radius
+}
+
+// Give a unit an upgrade's bonuses if it's one of the types it improves
+fn apply_upgrade(unit: &mut Unit, upgrade: &UpgradeDefinition) {
+    if upgrade.applies_to.contains(&unit.unit_type) {
+        unit.attack_damage += upgrade.attack_damage;
+        unit.armor += upgrade.armor;
+    }
+}
+
+// How close a worker has to be to the center of a building to work on it
+fn build_reach(building_radius: f32, worker: &Unit) -> f32 {
+    building_radius + worker.collision_radius() + NAV_CELL_SIZE
+}
+
+// Distance from a point to the edge of a unit, so big units can be hit anywhere along it
+fn edge_distance(x: f32, y: f32, unit: &Unit) -> f32 {
+    ((unit.x - x).powi(2) + (unit.y - y).powi(2)).sqrt() - unit.collision_radius()
+}
+
+// Share of an overlap each unit of a pair absorbs
+fn separation_weights(a: &Unit, b: &Unit) -> (f32, f32) {
+    let a_static = a.speed <= 0.0;
+    let b_static = b.speed <= 0.0;
+
+    match (a_static, b_static) {
+        (true, true) => (0.0, 0.0),
+        (true, false) => (0.0, 1.0),
+        (false, true) => (1.0, 0.0),
+        (false, false) => {
+            let a_moving = a.target_x.is_some();
+            let b_moving = b.target_x.is_some();
+
+            // A unit on the move pushes idle friendlies aside rather than being held up by them
+            if a.player_id == b.player_id && a_moving != b_moving {
+                if a_moving { (0.2, 0.8) } else { (0.8, 0.2) }
+            } else {
+                (0.5, 0.5)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::definitions::DEFINITIONS_PATH;
+    use crate::game::map::{MapResource, StartLocation, MAP_FORMAT_VERSION};
+
+    fn definitions() -> Definitions {
+        Definitions::load(DEFINITIONS_PATH).expect("shipped definitions are valid")
+    }
+
+    // A 2048x2048 grass map with a base in two opposite corners, leaving the
+    // middle free to set up fights and errands
+    fn open_field() -> Simulation {
+        field_with_terrain(vec![".".repeat(16); 16])
+    }
+
+    // The same, with 16x16 tiles of 128 pixels of the given terrain
+    fn field_with_terrain(terrain: Vec<String>) -> Simulation {
+        field(terrain, Vec::new())
+    }
+
+    // An open field with resource nodes to mine
+    fn field_with_resources(resources: Vec<MapResource>) -> Simulation {
+        field(vec![".".repeat(16); 16], resources)
+    }
+
+    fn field(terrain: Vec<String>, resources: Vec<MapResource>) -> Simulation {
+        let map = MapData {
+            version: MAP_FORMAT_VERSION,
+            name: "Open field".to_string(),
+            description: String::new(),
+            width: 2048.0,
+            height: 2048.0,
+            tile_size: 128.0,
+            terrain,
+            start_locations: vec![
+                StartLocation { x: 256.0, y: 256.0 },
+                StartLocation { x: 1792.0, y: 1792.0 },
+            ],
+            resources,
+            neutral_objects: Vec::new(),
+        };
+        Simulation::from_map(&map, 1, definitions())
+    }
+
+    // Tick until `done` holds, failing after `seconds` of game time
+    fn run_until(simulation: &mut Simulation, seconds: f32, mut done: impl FnMut(&Simulation) -> bool) {
+        let ticks = (seconds * TICK_RATE as f32) as u32;
+        for _ in 0..ticks {
+            if done(simulation) {
+                return;
+            }
+            simulation.tick(TICK_DT, &[]);
+        }
+        assert!(done(simulation), "still waiting after {} seconds", seconds);
+    }
+
+    fn unit(simulation: &Simulation, id: u32) -> &Unit {
+        simulation.units.iter().find(|unit| unit.id == id).expect("unit is alive")
+    }
+
+    // Gather, build, train and skirmish for a while, returning everything that
+    // has to come out the same on every run
+    fn play(seed: u64) -> String {
+        let mut simulation = Simulation::new(seed, definitions());
+
+        // A ranged fight in the middle, where misses and splash draw on the RNG
+        let (center_x, center_y) = (simulation.map_width / 2.0, simulation.map_height / 2.0);
+        let mut armies = [Vec::new(), Vec::new()];
+        for i in 0..6 {
+            let offset = i as f32 * 30.0;
+            armies[0].push(simulation.spawn_unit(UnitType::Ranger, center_x - 200.0, center_y - 75.0 + offset, 0));
+            armies[1].push(simulation.spawn_unit(UnitType::Tank, center_x + 200.0, center_y - 75.0 + offset, 1));
+        }
+
+        let mut opening = Vec::new();
+        for player_id in 0..2 {
+            let (hq_x, hq_y) = simulation.find_headquarters(player_id).unwrap();
+            let hq = simulation.units.iter()
+                .find(|unit| unit.player_id == player_id && unit.unit_type == UnitType::Headquarters)
+                .map(|unit| unit.id)
+                .unwrap();
+            let workers: Vec<u32> = simulation.units.iter()
+                .filter(|unit| unit.player_id == player_id && unit.unit_type == UnitType::Worker)
+                .map(|unit| unit.id)
+                .collect();
+            let node = simulation.find_nearest_resource(hq_x, hq_y, None).unwrap();
+
+            opening.push(PlayerCommand::new(player_id, workers[1..].to_vec(), Command::Gather { resource_id: node }));
+            opening.push(PlayerCommand::new(player_id, vec![workers[0]], Command::Build {
+                building_type: BuildingType::SupplyDepot,
+                x: hq_x + 150.0,
+                y: hq_y + 150.0,
+            }));
+            opening.push(PlayerCommand::new(player_id, vec![hq], Command::Train { unit_type: UnitType::Worker }));
+        }
+        opening.push(PlayerCommand::new(0, armies[0].to_vec(), Command::AttackMove { x: center_x + 200.0, y: center_y }));
+        opening.push(PlayerCommand::new(1, armies[1].to_vec(), Command::AttackMove { x: center_x - 200.0, y: center_y }));
+
+        simulation.tick(TICK_DT, &opening);
+        for _ in 0..TICK_RATE * 45 {
+            simulation.tick(TICK_DT, &[]);
+        }
+
+        format!("{} {:?} {:?} {:?}", simulation.tick_count, simulation.units, simulation.players, simulation.resource_nodes)
+    }
+
+    #[test]
+    fn same_seed_and_commands_replay_exactly() {
+        assert_eq!(play(42), play(42));
+    }
+
+    #[test]
+    fn units_walk_around_buildings() {
+        let mut simulation = open_field();
+        let barracks = simulation.spawn_building(BuildingType::Barracks, 1024.0, 1024.0, 0);
+        let footprint = unit(&simulation, barracks).footprint_radius();
+        let fighter = simulation.spawn_unit(UnitType::Fighter, 880.0, 1024.0, 0);
+
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![fighter], Command::Move { x: 1170.0, y: 1024.0 })]);
+        run_until(&mut simulation, 20.0, |simulation| {
+            let walker = unit(simulation, fighter);
+            let distance = ((walker.x - 1024.0).powi(2) + (walker.y - 1024.0).powi(2)).sqrt();
+            assert!(distance > footprint - 8.0, "walked through the barracks at ({}, {})", walker.x, walker.y);
+            walker.orders.is_empty()
+        });
+        let walker = unit(&simulation, fighter);
+        assert!((walker.x - 1170.0).abs() < 20.0 && (walker.y - 1024.0).abs() < 20.0);
+    }
+
+    #[test]
+    fn large_group_moves_share_one_flow_field() {
+        let mut simulation = open_field();
+        let group: Vec<u32> = (0..GROUP_MOVE_THRESHOLD)
+            .map(|i| simulation.spawn_unit(UnitType::Fighter, 700.0 + (i % 5) as f32 * 30.0, 700.0 + (i / 5) as f32 * 30.0, 0))
+            .collect();
+
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, group.clone(), Command::Move { x: 1300.0, y: 1200.0 })]);
+        assert_eq!(simulation.flow_fields.len(), 1);
+        let field = simulation.flow_fields[0].id;
+        assert!(group.iter().all(|&id| unit(&simulation, id).flow_field == Some(field)));
+
+        // Everyone arrives and the field is dropped once nobody follows it
+        run_until(&mut simulation, 30.0, |simulation| group.iter().all(|&id| unit(simulation, id).orders.is_empty()));
+        assert!(simulation.flow_fields.is_empty());
+        for &id in &group {
+            let arrived = unit(&simulation, id);
+            assert!(((arrived.x - 1300.0).powi(2) + (arrived.y - 1200.0).powi(2)).sqrt() < 150.0);
+        }
+    }
+
+    #[test]
+    fn stacked_units_spread_apart() {
+        let mut simulation = open_field();
+        let a = simulation.spawn_unit(UnitType::Fighter, 1000.0, 1000.0, 0);
+        let b = simulation.spawn_unit(UnitType::Fighter, 1000.0, 1000.0, 0);
+        let c = simulation.spawn_unit(UnitType::Tank, 1005.0, 1000.0, 0);
+
+        for _ in 0..TICK_RATE {
+            simulation.tick(TICK_DT, &[]);
+        }
+        for (first, second) in [(a, b), (a, c), (b, c)] {
+            let (first, second) = (unit(&simulation, first), unit(&simulation, second));
+            let distance = ((first.x - second.x).powi(2) + (first.y - second.y).powi(2)).sqrt();
+            let min_distance = first.collision_radius() + second.collision_radius();
+            assert!(distance >= min_distance * 0.9, "units {} and {} still overlap at {}", first.id, second.id, distance);
+        }
+    }
+
+    #[test]
+    fn buildings_are_never_pushed() {
+        let mut simulation = open_field();
+        let depot = simulation.spawn_building(BuildingType::SupplyDepot, 1000.0, 1000.0, 0);
+        let fighter = simulation.spawn_unit(UnitType::Fighter, 1010.0, 1000.0, 0);
+
+        for _ in 0..TICK_RATE {
+            simulation.tick(TICK_DT, &[]);
+        }
+        assert_eq!((unit(&simulation, depot).x, unit(&simulation, depot).y), (1000.0, 1000.0));
+        assert!(unit(&simulation, fighter).x > 1010.0);
+    }
+
+    #[test]
+    fn moving_units_shove_idle_friends_aside() {
+        let mut simulation = open_field();
+        let walker = simulation.spawn_unit(UnitType::Fighter, 1000.0, 1000.0, 0);
+        let idle = simulation.spawn_unit(UnitType::Fighter, 1010.0, 1000.0, 0);
+        let enemy = simulation.spawn_unit(UnitType::Fighter, 1010.0, 1000.0, 1);
+
+        let mut moving = unit(&simulation, walker).clone();
+        moving.target_x = Some(1500.0);
+        moving.target_y = Some(1000.0);
+        assert_eq!(separation_weights(&moving, unit(&simulation, idle)), (0.2, 0.8));
+        assert_eq!(separation_weights(unit(&simulation, idle), &moving), (0.8, 0.2));
+        // Enemies shove each other evenly
+        assert_eq!(separation_weights(&moving, unit(&simulation, enemy)), (0.5, 0.5));
+    }
+
+    #[test]
+    fn sand_slows_units_and_water_stops_them() {
+        // Row 8 is sand, row 10 has a lake in the middle
+        let mut terrain = vec![".".repeat(16); 16];
+        terrain[8] = "s".repeat(16);
+        terrain[10] = "....~~~~~~~~....".to_string();
+        let mut simulation = field_with_terrain(terrain);
+        let on_grass = simulation.spawn_unit(UnitType::Fighter, 300.0, 900.0, 0);
+        let on_sand = simulation.spawn_unit(UnitType::Fighter, 300.0, 1090.0, 0);
+        let swimmer = simulation.spawn_unit(UnitType::Fighter, 1000.0, 1250.0, 0);
+
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(0, vec![on_grass], Command::Move { x: 1700.0, y: 900.0 }),
+            PlayerCommand::new(0, vec![on_sand], Command::Move { x: 1700.0, y: 1090.0 }),
+            PlayerCommand::new(0, vec![swimmer], Command::Move { x: 1000.0, y: 1400.0 }),
+        ]);
+        for _ in 0..TICK_RATE * 2 {
+            simulation.tick(TICK_DT, &[]);
+        }
+
+        let grass_distance = unit(&simulation, on_grass).x - 300.0;
+        let sand_distance = unit(&simulation, on_sand).x - 300.0;
+        assert!((sand_distance / grass_distance - 0.7).abs() < 0.05, "sand {} vs grass {}", sand_distance, grass_distance);
+        assert!(simulation.nav_grid.is_walkable(unit(&simulation, swimmer).x, unit(&simulation, swimmer).y));
+        assert!(simulation.terrain.is_passable(unit(&simulation, swimmer).x, unit(&simulation, swimmer).y));
+    }
+
+    #[test]
+    #[should_panic(expected = "map Broken has broken terrain")]
+    fn unvalidated_broken_terrain_is_not_hidden() {
+        let map = MapData {
+            version: MAP_FORMAT_VERSION,
+            name: "Broken".to_string(),
+            description: String::new(),
+            width: 256.0,
+            height: 256.0,
+            tile_size: 128.0,
+            terrain: vec!["..".to_string(), ".".to_string()],
+            start_locations: vec![StartLocation { x: 64.0, y: 64.0 }],
+            resources: Vec::new(),
+            neutral_objects: Vec::new(),
+        };
+        Simulation::from_map(&map, 1, definitions());
+    }
+
+    #[test]
+    fn shift_queued_orders_run_in_turn() {
+        let mut simulation = open_field();
+        let fighter = simulation.spawn_unit(UnitType::Fighter, 700.0, 700.0, 0);
+
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(0, vec![fighter], Command::Move { x: 1000.0, y: 700.0 }),
+            PlayerCommand::queued(0, vec![fighter], Command::Move { x: 1000.0, y: 1000.0 }),
+        ]);
+        assert_eq!(unit(&simulation, fighter).orders.len(), 2);
+
+        // It reaches the first waypoint before turning for the second
+        run_until(&mut simulation, 20.0, |simulation| unit(simulation, fighter).orders.len() == 1);
+        let walker = unit(&simulation, fighter);
+        assert!((walker.x - 1000.0).abs() < 32.0 && (walker.y - 700.0).abs() < 32.0);
+        run_until(&mut simulation, 20.0, |simulation| unit(simulation, fighter).orders.is_empty());
+        let walker = unit(&simulation, fighter);
+        assert!((walker.x - 1000.0).abs() < 32.0 && (walker.y - 1000.0).abs() < 32.0);
+
+        // A plain order replaces the whole queue
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(0, vec![fighter], Command::Move { x: 700.0, y: 1000.0 }),
+            PlayerCommand::queued(0, vec![fighter], Command::Move { x: 700.0, y: 700.0 }),
+        ]);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![fighter], Command::Move { x: 1200.0, y: 1000.0 })]);
+        assert_eq!(unit(&simulation, fighter).orders, [Command::Move { x: 1200.0, y: 1000.0 }]);
+    }
+
+    #[test]
+    fn patrols_walk_back_and_forth() {
+        let mut simulation = open_field();
+        let fighter = simulation.spawn_unit(UnitType::Fighter, 700.0, 1000.0, 0);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![fighter], Command::Patrol { x: 1100.0, y: 1000.0 })]);
+
+        // Out to the point, then back to where the patrol started, and out again
+        for (x, y) in [(700.0, 1000.0), (1100.0, 1000.0), (700.0, 1000.0)] {
+            run_until(&mut simulation, 20.0, |simulation| unit(simulation, fighter).orders.front() == Some(&Command::Patrol { x, y }));
+            let walker = unit(&simulation, fighter);
+            assert!((walker.x - (1800.0 - x)).abs() < 32.0, "turned round at {}", walker.x);
+            assert_eq!(walker.orders.len(), 1);
+        }
+    }
+
+    #[test]
+    fn attack_orders_chase_their_target_past_closer_enemies() {
+        let mut simulation = open_field();
+        let fighter = simulation.spawn_unit(UnitType::Fighter, 1000.0, 1000.0, 0);
+        let bystander = simulation.spawn_unit(UnitType::Worker, 1000.0, 1080.0, 1);
+        let target = simulation.spawn_unit(UnitType::Worker, 1200.0, 1000.0, 1);
+        simulation.tick(TICK_DT, &[]);
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(1, vec![target], Command::Move { x: 1200.0, y: 1400.0 }),
+            PlayerCommand::new(0, vec![fighter], Command::Attack { target_id: target }),
+        ]);
+
+        run_until(&mut simulation, 30.0, |simulation| {
+            assert_eq!(unit(simulation, bystander).health, 50.0, "hit the closer worker");
+            !simulation.units.iter().any(|unit| unit.id == target)
+        });
+        simulation.tick(TICK_DT, &[]);
+        assert!(!unit(&simulation, fighter).orders.contains(&Command::Attack { target_id: target }));
+    }
+
+    #[test]
+    fn attack_orders_end_when_the_target_leaves_sight() {
+        let mut simulation = open_field();
+        let worker = simulation.spawn_unit(UnitType::Worker, 1000.0, 800.0, 0);
+        let fighter = simulation.spawn_unit(UnitType::Fighter, 1000.0, 900.0, 1);
+        simulation.tick(TICK_DT, &[]);
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(1, vec![fighter], Command::Move { x: 1000.0, y: 1700.0 }),
+            PlayerCommand::new(0, vec![worker], Command::Attack { target_id: fighter }),
+        ]);
+        simulation.tick(TICK_DT, &[]);
+        assert_eq!(unit(&simulation, worker).attack_target, Some(fighter));
+
+        // The faster fighter gets away and the worker gives up rather than
+        // following it into the fog
+        run_until(&mut simulation, 20.0, |simulation| unit(simulation, worker).orders.is_empty());
+        let (chaser, runaway) = (unit(&simulation, worker), unit(&simulation, fighter));
+        assert!(runaway.y > chaser.y + 150.0);
+        assert!(!simulation.view(0).can_see(runaway));
+    }
+
+    // A fighter with `stance` and a passive enemy worker `gap` pixels to its right
+    fn standoff(stance: Stance, gap: f32) -> (Simulation, u32, u32) {
+        let mut simulation = open_field();
+        let fighter = simulation.spawn_unit(UnitType::Fighter, 1000.0, 1000.0, 0);
+        let worker = simulation.spawn_unit(UnitType::Worker, 1000.0 + gap, 1000.0, 1);
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(0, vec![fighter], Command::SetStance { stance }),
+            PlayerCommand::new(1, vec![worker], Command::SetStance { stance: Stance::Passive }),
+        ]);
+        (simulation, fighter, worker)
+    }
+
+    #[test]
+    fn aggressive_units_chase_enemies_in_sight() {
+        let (mut simulation, fighter, worker) = standoff(Stance::Aggressive, 150.0);
+        run_until(&mut simulation, 10.0, |simulation| unit(simulation, worker).health < 50.0);
+        assert!(unit(&simulation, fighter).x > 1050.0);
+    }
+
+    #[test]
+    fn units_holding_position_only_shoot_what_is_in_range() {
+        let (mut simulation, fighter, worker) = standoff(Stance::HoldPosition, 150.0);
+        for _ in 0..TICK_RATE * 5 {
+            simulation.tick(TICK_DT, &[]);
+        }
+        assert_eq!((unit(&simulation, fighter).x, unit(&simulation, fighter).y), (1000.0, 1000.0));
+        assert_eq!(unit(&simulation, worker).health, 50.0);
+
+        let (mut simulation, fighter, worker) = standoff(Stance::HoldPosition, 40.0);
+        run_until(&mut simulation, 5.0, |simulation| unit(simulation, worker).health < 50.0);
+        assert_eq!((unit(&simulation, fighter).x, unit(&simulation, fighter).y), (1000.0, 1000.0));
+    }
+
+    #[test]
+    fn passive_units_never_fire_on_their_own() {
+        let (mut simulation, fighter, worker) = standoff(Stance::Passive, 40.0);
+        for _ in 0..TICK_RATE * 5 {
+            simulation.tick(TICK_DT, &[]);
+        }
+        assert_eq!(unit(&simulation, worker).health, 50.0);
+
+        // Told to, they do
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![fighter], Command::Attack { target_id: worker })]);
+        run_until(&mut simulation, 5.0, |simulation| unit(simulation, worker).health < 50.0);
+    }
+
+    #[test]
+    fn cancelled_construction_refunds_most_of_the_cost() {
+        let mut simulation = open_field();
+        let worker = simulation.spawn_unit(UnitType::Worker, 900.0, 1000.0, 0);
+        let minerals = simulation.players[0].minerals;
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![worker], Command::Build {
+            building_type: BuildingType::Barracks,
+            x: 1000.0,
+            y: 1000.0,
+        })]);
+
+        // Paid for once the foundation goes down
+        let foundation = |simulation: &Simulation| simulation.units.iter()
+            .find(|unit| unit.player_id == 0 && unit.building_type == Some(BuildingType::Barracks))
+            .map(|unit| unit.id);
+        run_until(&mut simulation, 10.0, |simulation| foundation(simulation).is_some());
+        let barracks = foundation(&simulation).unwrap();
+        assert_eq!(simulation.players[0].minerals, minerals - 150);
+
+        // Someone else's cancel does nothing
+        simulation.tick(TICK_DT, &[PlayerCommand::new(1, vec![barracks], Command::CancelConstruction)]);
+        assert!(foundation(&simulation).is_some());
+
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![barracks], Command::CancelConstruction)]);
+        assert_eq!(simulation.players[0].minerals, minerals - 150 + 112);
+        assert!(foundation(&simulation).is_none());
+    }
+
+    #[test]
+    fn finished_buildings_cannot_be_cancelled() {
+        let mut simulation = open_field();
+        let depot = simulation.spawn_building(BuildingType::SupplyDepot, 1000.0, 1000.0, 0);
+        let minerals = simulation.players[0].minerals;
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![depot], Command::CancelConstruction)]);
+        simulation.tick(TICK_DT, &[]);
+        assert_eq!(unit(&simulation, depot).health, unit(&simulation, depot).max_health);
+        assert_eq!(simulation.players[0].minerals, minerals);
+    }
+
+    #[test]
+    fn buildings_only_go_on_open_ground() {
+        // Row 10 has a lake in the middle
+        let mut terrain = vec![".".repeat(16); 16];
+        terrain[10] = "....~~~~~~~~....".to_string();
+        let mut simulation = field_with_terrain(terrain);
+        simulation.spawn_building(BuildingType::SupplyDepot, 1000.0, 700.0, 1);
+
+        assert!(simulation.can_place_building(&BuildingType::Barracks, 1000.0, 1000.0));
+        assert!(!simulation.can_place_building(&BuildingType::Barracks, 1000.0, 1344.0), "in the lake");
+        assert!(!simulation.can_place_building(&BuildingType::Barracks, 1000.0, 1260.0), "hanging over the shore");
+        assert!(!simulation.can_place_building(&BuildingType::Barracks, 20.0, 1000.0), "off the map edge");
+        assert!(!simulation.can_place_building(&BuildingType::Barracks, 1050.0, 700.0), "on top of the depot");
+        assert!(simulation.can_place_building(&BuildingType::Barracks, 1150.0, 700.0));
+
+        // A build order into the lake is turned down without paying for it
+        let worker = simulation.spawn_unit(UnitType::Worker, 1000.0, 1000.0, 0);
+        let minerals = simulation.players[0].minerals;
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![worker], Command::Build {
+            building_type: BuildingType::Barracks,
+            x: 1000.0,
+            y: 1344.0,
+        })]);
+        run_until(&mut simulation, 30.0, |simulation| unit(simulation, worker).orders.is_empty());
+        assert_eq!(simulation.players[0].minerals, minerals);
+        assert!(!simulation.units.iter().any(|unit| unit.building_type == Some(BuildingType::Barracks)));
+    }
+
+    // A finished Barracks for player 0 with money and supply to spare
+    fn barracks_with_funds() -> (Simulation, u32) {
+        let mut simulation = open_field();
+        let barracks = simulation.spawn_building(BuildingType::Barracks, 1000.0, 1000.0, 0);
+        simulation.spawn_building(BuildingType::SupplyDepot, 1200.0, 1000.0, 0);
+        simulation.spawn_building(BuildingType::SupplyDepot, 1200.0, 1200.0, 0);
+        simulation.players[0].minerals = 2000;
+        simulation.players[0].energy = 2000;
+        simulation.tick(TICK_DT, &[]);
+        (simulation, barracks)
+    }
+
+    fn count(simulation: &Simulation, unit_type: UnitType) -> usize {
+        simulation.units.iter().filter(|unit| unit.player_id == 0 && unit.unit_type == unit_type).count()
+    }
+
+    #[test]
+    fn training_is_paid_up_front_and_delivered_in_order() {
+        let (mut simulation, barracks) = barracks_with_funds();
+        let supply = simulation.players[0].supply_used;
+        let train = |unit_type| PlayerCommand::new(0, vec![barracks], Command::Train { unit_type });
+        simulation.tick(TICK_DT, &[train(UnitType::Fighter), train(UnitType::Ranger)]);
+
+        assert_eq!(unit(&simulation, barracks).production_queue, [UnitType::Fighter, UnitType::Ranger]);
+        assert_eq!((simulation.players[0].minerals, simulation.players[0].energy), (2000 - 180, 2000 - 60));
+        assert_eq!(simulation.players[0].supply_used, supply + 2);
+
+        // Fighters take 18 seconds, then the Ranger another 20
+        run_until(&mut simulation, 19.0, |simulation| count(simulation, UnitType::Fighter) == 1);
+        assert_eq!(count(&simulation, UnitType::Ranger), 0);
+        run_until(&mut simulation, 21.0, |simulation| count(simulation, UnitType::Ranger) == 1);
+        assert!(unit(&simulation, barracks).production_queue.is_empty());
+    }
+
+    #[test]
+    fn buildings_only_train_their_own_units_up_to_the_queue_limit() {
+        let (mut simulation, barracks) = barracks_with_funds();
+        let train = |unit_type| PlayerCommand::new(0, vec![barracks], Command::Train { unit_type });
+        simulation.tick(TICK_DT, &[train(UnitType::Worker), train(UnitType::Tank)]);
+        assert!(unit(&simulation, barracks).production_queue.is_empty());
+        assert_eq!(simulation.players[0].minerals, 2000);
+
+        let orders = vec![train(UnitType::Fighter); PRODUCTION_QUEUE_LIMIT + 2];
+        simulation.tick(TICK_DT, &orders);
+        assert_eq!(unit(&simulation, barracks).production_queue.len(), PRODUCTION_QUEUE_LIMIT);
+        assert_eq!(simulation.players[0].minerals, 2000 - 100 * PRODUCTION_QUEUE_LIMIT as i32);
+    }
+
+    #[test]
+    fn cancelled_training_is_refunded_in_full() {
+        let (mut simulation, barracks) = barracks_with_funds();
+        let supply = simulation.players[0].supply_used;
+        let train = |unit_type| PlayerCommand::new(0, vec![barracks], Command::Train { unit_type });
+        simulation.tick(TICK_DT, &[train(UnitType::Fighter), train(UnitType::Ranger)]);
+        for _ in 0..TICK_RATE * 5 {
+            simulation.tick(TICK_DT, &[]);
+        }
+
+        // Out of the back of the queue, then the half-trained front
+        let cancel = |slot| PlayerCommand::new(0, vec![barracks], Command::CancelTraining { slot });
+        simulation.tick(TICK_DT, &[cancel(1)]);
+        assert_eq!(unit(&simulation, barracks).production_queue, [UnitType::Fighter]);
+        assert_eq!((simulation.players[0].minerals, simulation.players[0].energy), (2000 - 100, 2000 - 20));
+        simulation.tick(TICK_DT, &[cancel(0)]);
+        assert!(unit(&simulation, barracks).production_queue.is_empty());
+        assert_eq!(unit(&simulation, barracks).production_progress, 0.0);
+        assert_eq!((simulation.players[0].minerals, simulation.players[0].energy), (2000, 2000));
+        assert_eq!(simulation.players[0].supply_used, supply);
+    }
+
+    #[test]
+    fn trained_units_head_for_the_rally_point() {
+        let (mut simulation, barracks) = barracks_with_funds();
+        let depot = simulation.units.iter()
+            .find(|unit| unit.building_type == Some(BuildingType::SupplyDepot))
+            .map(|unit| unit.id)
+            .unwrap();
+        let rally_point = RallyPoint::Position { x: 1500.0, y: 600.0 };
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(0, vec![barracks, depot], Command::SetRallyPoint { rally_point: rally_point.clone() }),
+            PlayerCommand::new(0, vec![barracks], Command::Train { unit_type: UnitType::Fighter }),
+        ]);
+        assert_eq!(unit(&simulation, barracks).rally_point, Some(rally_point));
+        assert_eq!(unit(&simulation, depot).rally_point, None, "depots don't train anything");
+
+        run_until(&mut simulation, 19.0, |simulation| count(simulation, UnitType::Fighter) == 1);
+        let fighter = simulation.units.iter().find(|unit| unit.unit_type == UnitType::Fighter).unwrap().id;
+        assert_eq!(unit(&simulation, fighter).orders, [Command::Move { x: 1500.0, y: 600.0 }]);
+        run_until(&mut simulation, 10.0, |simulation| unit(simulation, fighter).orders.is_empty());
+        let fighter = unit(&simulation, fighter);
+        assert!((fighter.x - 1500.0).abs() < 32.0 && (fighter.y - 600.0).abs() < 32.0);
+    }
+
+    #[test]
+    fn workers_rallied_to_a_node_start_mining_it() {
+        let mut simulation = Simulation::new(1, definitions());
+        let (hq_x, hq_y) = simulation.find_headquarters(0).unwrap();
+        let hq = simulation.units.iter()
+            .find(|unit| unit.player_id == 0 && unit.unit_type == UnitType::Headquarters)
+            .map(|unit| unit.id)
+            .unwrap();
+        let node = simulation.find_nearest_resource(hq_x, hq_y, None).unwrap();
+        let workers: Vec<u32> = simulation.units.iter()
+            .filter(|unit| unit.player_id == 0 && unit.unit_type == UnitType::Worker)
+            .map(|unit| unit.id)
+            .collect();
+
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(0, vec![hq], Command::SetRallyPoint { rally_point: RallyPoint::Resource { resource_id: node } }),
+            PlayerCommand::new(0, vec![hq], Command::Train { unit_type: UnitType::Worker }),
+        ]);
+        let new_worker = |simulation: &Simulation| simulation.units.iter()
+            .find(|unit| unit.unit_type == UnitType::Worker && unit.player_id == 0 && !workers.contains(&unit.id))
+            .map(|unit| unit.id);
+        run_until(&mut simulation, 13.0, |simulation| new_worker(simulation).is_some());
+        let worker = new_worker(&simulation).unwrap();
+        assert_eq!(unit(&simulation, worker).orders, [Command::Gather { resource_id: node }]);
+        run_until(&mut simulation, 20.0, |simulation| unit(simulation, worker).current_resources.unwrap_or(0) > 0);
+    }
+
+    #[test]
+    fn energy_goes_to_the_nearest_depot() {
+        let mut simulation = field_with_resources(vec![
+            MapResource { x: 1000.0, y: 1000.0, resource_type: ResourceType::Energy, amount: 1000, radius: 25.0 },
+        ]);
+        let depot = simulation.spawn_building(BuildingType::ResourceDepot, 1150.0, 1000.0, 0);
+        let worker = simulation.spawn_unit(UnitType::Worker, 950.0, 1000.0, 0);
+        let node = simulation.resource_nodes[0].id;
+        let (minerals, energy) = (simulation.players[0].minerals, simulation.players[0].energy);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![worker], Command::Gather { resource_id: node })]);
+
+        run_until(&mut simulation, 10.0, |simulation| unit(simulation, worker).carried_resource.is_some());
+        assert_eq!(unit(&simulation, worker).carried_resource, Some(ResourceType::Energy));
+
+        // Dropped off at the depot next door rather than the far-off headquarters
+        run_until(&mut simulation, 10.0, |simulation| simulation.players[0].energy > energy);
+        let (drop_off, carrier) = (unit(&simulation, depot), unit(&simulation, worker));
+        assert!(((carrier.x - drop_off.x).powi(2) + (carrier.y - drop_off.y).powi(2)).sqrt() < 100.0);
+        assert_eq!(simulation.players[0].energy, energy + 50);
+        assert_eq!(simulation.players[0].minerals, minerals);
+
+        // Then back to the same node for more
+        assert_eq!(carrier.orders, [Command::Gather { resource_id: node }]);
+        assert_eq!(carrier.gather_phase, Some(GatherPhase::ToNode));
+    }
+
+    #[test]
+    fn crowded_nodes_send_workers_to_the_next_one() {
+        let mut simulation = field_with_resources(vec![
+            MapResource { x: 1000.0, y: 1000.0, resource_type: ResourceType::Minerals, amount: 1000, radius: 25.0 },
+            MapResource { x: 1000.0, y: 1150.0, resource_type: ResourceType::Minerals, amount: 1000, radius: 25.0 },
+        ]);
+        let (first, second) = (simulation.resource_nodes[0].id, simulation.resource_nodes[1].id);
+
+        // Enemy miners on the node don't count against the limit
+        let enemies: Vec<u32> = (0..NODE_WORKER_LIMIT).map(|i| simulation.spawn_unit(UnitType::Worker, 900.0 + i as f32 * 40.0, 900.0, 1)).collect();
+        let workers: Vec<u32> = (0..=NODE_WORKER_LIMIT).map(|i| simulation.spawn_unit(UnitType::Worker, 900.0 + i as f32 * 40.0, 850.0, 0)).collect();
+        simulation.tick(TICK_DT, &[PlayerCommand::new(1, enemies, Command::Gather { resource_id: first })]);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, workers.clone(), Command::Gather { resource_id: first })]);
+        simulation.tick(TICK_DT, &[]);
+
+        let on = |resource_id| workers.iter()
+            .filter(|&&id| unit(&simulation, id).orders.front() == Some(&Command::Gather { resource_id }))
+            .count();
+        assert_eq!((on(first), on(second)), (NODE_WORKER_LIMIT, 1));
+    }
+
+    #[test]
+    fn workers_move_on_when_a_node_runs_dry() {
+        let mut simulation = field_with_resources(vec![
+            MapResource { x: 1000.0, y: 1000.0, resource_type: ResourceType::Minerals, amount: 50, radius: 25.0 },
+            MapResource { x: 1000.0, y: 1150.0, resource_type: ResourceType::Minerals, amount: 1000, radius: 25.0 },
+        ]);
+        simulation.spawn_building(BuildingType::ResourceDepot, 1150.0, 1000.0, 0);
+        let (first, second) = (simulation.resource_nodes[0].id, simulation.resource_nodes[1].id);
+        let worker = simulation.spawn_unit(UnitType::Worker, 950.0, 1000.0, 0);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![worker], Command::Gather { resource_id: first })]);
+
+        // One load empties it; it's left as a depleted husk for a while, then removed
+        run_until(&mut simulation, 20.0, |simulation| unit(simulation, worker).orders.front() == Some(&Command::Gather { resource_id: second }));
+        let husk = simulation.resource_node(first).unwrap();
+        assert_eq!(husk.resources, 0);
+        assert!(husk.depleted_at.is_some());
+        run_until(&mut simulation, DEPLETED_NODE_LINGER + 1.0, |simulation| simulation.resource_node(first).is_none());
+
+        run_until(&mut simulation, 20.0, |simulation| simulation.resource_node(second).unwrap().resources < 1000);
+    }
+
+    #[test]
+    fn tanks_wait_for_a_research_center() {
+        let (mut simulation, _) = barracks_with_funds();
+        let factory = simulation.spawn_building(BuildingType::Factory, 1000.0, 1300.0, 0);
+        let train_tank = PlayerCommand::new(0, vec![factory], Command::Train { unit_type: UnitType::Tank });
+        simulation.tick(TICK_DT, std::slice::from_ref(&train_tank));
+        assert!(unit(&simulation, factory).production_queue.is_empty());
+        assert_eq!(simulation.players[0].minerals, 2000);
+        assert_eq!(simulation.missing_prerequisites(0, &simulation.definitions.unit(&UnitType::Tank).prerequisites), [BuildingType::ResearchCenter]);
+
+        simulation.spawn_building(BuildingType::ResearchCenter, 1300.0, 1300.0, 0);
+        simulation.tick(TICK_DT, &[train_tank]);
+        assert_eq!(unit(&simulation, factory).production_queue, [UnitType::Tank]);
+        assert_eq!(simulation.players[0].minerals, 2000 - 200);
+    }
+
+    #[test]
+    fn upgrades_wait_for_their_prerequisites() {
+        let (mut simulation, _) = barracks_with_funds();
+        let center = simulation.spawn_building(BuildingType::ResearchCenter, 1300.0, 1300.0, 0);
+        let tank = simulation.spawn_unit(UnitType::Tank, 700.0, 700.0, 0);
+        let research = PlayerCommand::new(0, vec![center], Command::Research { upgrade: UpgradeType::VehiclePlating });
+        simulation.tick(TICK_DT, std::slice::from_ref(&research));
+        assert_eq!(unit(&simulation, center).research, None);
+        assert!(!simulation.can_research(0, &UpgradeType::VehiclePlating));
+
+        // Plating needs a Factory; once researched every tank wears it
+        simulation.spawn_building(BuildingType::Factory, 1000.0, 1300.0, 0);
+        simulation.tick(TICK_DT, &[research]);
+        assert_eq!(unit(&simulation, center).research, Some(UpgradeType::VehiclePlating));
+        assert_eq!((simulation.players[0].minerals, simulation.players[0].energy), (2000 - 150, 2000 - 100));
+        let armor = unit(&simulation, tank).armor;
+        run_until(&mut simulation, 51.0, |simulation| simulation.players[0].upgrades.contains(&UpgradeType::VehiclePlating));
+        assert_eq!(unit(&simulation, tank).armor, armor + 3.0);
+        assert!(!simulation.can_research(0, &UpgradeType::VehiclePlating), "already researched");
+    }
+
+    #[test]
+    fn shots_land_after_flying_to_their_target() {
+        let mut simulation = open_field();
+        let ranger = simulation.spawn_unit(UnitType::Ranger, 1000.0, 1000.0, 0);
+        let worker = simulation.spawn_unit(UnitType::Worker, 1070.0, 1000.0, 1);
+        simulation.tick(TICK_DT, &[]);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![ranger], Command::Attack { target_id: worker })]);
+
+        run_until(&mut simulation, 3.0, |simulation| !simulation.projectiles.is_empty());
+        assert_eq!(simulation.projectiles[0].target_id, Some(worker));
+        assert_eq!(unit(&simulation, worker).health, 50.0, "hit before the shot landed");
+        run_until(&mut simulation, 1.0, |simulation| simulation.projectiles.is_empty());
+        assert_eq!(unit(&simulation, worker).health, 50.0 - 20.0 * 1.5);
+        assert_eq!(simulation.impacts.len(), 1);
+    }
+
+    #[test]
+    fn moving_targets_dodge_shots() {
+        let mut simulation = open_field();
+        let ranger = simulation.spawn_unit(UnitType::Ranger, 1000.0, 1000.0, 0);
+        let worker = simulation.spawn_unit(UnitType::Worker, 1000.0, 1060.0, 1);
+        simulation.units.iter_mut().find(|unit| unit.id == ranger).unwrap().moving_miss_chance = 1.0;
+        simulation.tick(TICK_DT, &[
+            PlayerCommand::new(0, vec![ranger], Command::SetStance { stance: Stance::Passive }),
+            PlayerCommand::new(1, vec![worker], Command::Move { x: 1000.0, y: 1900.0 }),
+        ]);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![ranger], Command::Attack { target_id: worker })]);
+
+        // Every shot at the fleeing worker goes wide and lands where it was
+        let (mut shots, mut in_flight) = (0, 0);
+        run_until(&mut simulation, 6.0, |simulation| {
+            assert!(simulation.projectiles.iter().all(|projectile| projectile.target_id.is_none()));
+            shots += simulation.projectiles.len().saturating_sub(in_flight);
+            in_flight = simulation.projectiles.len();
+            shots >= 2
+        });
+        assert_eq!(unit(&simulation, worker).health, 50.0);
+    }
+
+    #[test]
+    fn tank_shells_splash_nearby_enemies_only() {
+        let mut simulation = open_field();
+        let tank = simulation.spawn_unit(UnitType::Tank, 1000.0, 1000.0, 0);
+        let target = simulation.spawn_unit(UnitType::Worker, 1060.0, 1000.0, 1);
+        let neighbour = simulation.spawn_unit(UnitType::Worker, 1060.0, 1032.0, 1);
+        let friend = simulation.spawn_unit(UnitType::Worker, 1060.0, 968.0, 0);
+        let bystander = simulation.spawn_unit(UnitType::Worker, 1060.0, 1120.0, 1);
+        simulation.tick(TICK_DT, &[]);
+        simulation.tick(TICK_DT, &[PlayerCommand::new(0, vec![tank], Command::Attack { target_id: target })]);
+
+        run_until(&mut simulation, 3.0, |simulation| unit(simulation, target).health < 50.0);
+        assert_eq!(unit(&simulation, target).health, 50.0 - 30.0 * 0.75);
+        assert_eq!(unit(&simulation, neighbour).health, 50.0 - 30.0 * 0.75 * SPLASH_DAMAGE_SHARE);
+        assert_eq!(unit(&simulation, friend).health, 50.0, "splashed its own side");
+        assert_eq!(unit(&simulation, bystander).health, 50.0);
+    }
+
+    // Who a unit fires at over `seconds`, one entry per shot
+    fn shots_fired(simulation: &mut Simulation, shooter: u32, seconds: f32) -> Vec<u32> {
+        let mut targets = Vec::new();
+        for _ in 0..(seconds * TICK_RATE as f32) as u32 {
+            let cooldown = unit(simulation, shooter).current_cooldown;
+            simulation.tick(TICK_DT, &[]);
+            let shooter = unit(simulation, shooter);
+            if shooter.current_cooldown > cooldown {
+                targets.extend(shooter.last_target);
+            }
+        }
+        targets
+    }
+
+    #[test]
+    fn turrets_fire_on_enemies_in_range_as_cooldown_allows() {
+        let mut simulation = open_field();
+        let turret = simulation.spawn_building(BuildingType::DefenseTurret, 1000.0, 1000.0, 0);
+        let near = simulation.spawn_building(BuildingType::SupplyDepot, 1150.0, 1000.0, 1);
+        simulation.spawn_building(BuildingType::SupplyDepot, 1000.0, 1300.0, 1);
+
+        // A 1.2 second cooldown fits three shots in three seconds, all at the
+        // depot in range rather than the one beyond it
+        assert_eq!(shots_fired(&mut simulation, turret, 3.0), [near; 3]);
+    }
+
+    #[test]
+    fn turrets_go_for_enemies_attacking_friendlies_first() {
+        let mut simulation = open_field();
+        let turret = simulation.spawn_building(BuildingType::DefenseTurret, 1000.0, 1000.0, 0);
+        let depot = simulation.spawn_building(BuildingType::SupplyDepot, 1100.0, 1000.0, 1);
+        let raider = simulation.spawn_unit(UnitType::Fighter, 1000.0, 1170.0, 1);
+        simulation.spawn_unit(UnitType::Worker, 1000.0, 1210.0, 0);
+        assert_eq!(shots_fired(&mut simulation, turret, 0.1), [depot], "closest first while nobody's under attack");
+
+        // Once the raider hits the worker, the turret switches to it
+        run_until(&mut simulation, 3.0, |simulation| unit(simulation, raider).last_target.is_some());
+        let shots = shots_fired(&mut simulation, turret, 2.5);
+        assert!(shots.len() >= 2 && shots.iter().all(|&target| target == raider), "{:?}", shots);
+    }
+
+    #[test]
+    fn training_stops_at_the_supply_cap() {
+        let mut simulation = open_field();
+        simulation.players[0].minerals = 2000;
+        simulation.tick(TICK_DT, &[]);
+        let hq = simulation.units.iter()
+            .find(|unit| unit.player_id == 0 && unit.unit_type == UnitType::Headquarters)
+            .map(|unit| unit.id)
+            .unwrap();
+        let player = &simulation.players[0];
+        assert_eq!(player.supply_cap, 10);
+        for i in player.supply_used..player.supply_cap {
+            simulation.spawn_unit(UnitType::Fighter, 700.0 + i as f32 * 40.0, 700.0, 0);
+        }
+        simulation.tick(TICK_DT, &[]);
+        assert!(!simulation.has_supply(0, &UnitType::Worker));
+
+        let train_worker = PlayerCommand::new(0, vec![hq], Command::Train { unit_type: UnitType::Worker });
+        simulation.tick(TICK_DT, std::slice::from_ref(&train_worker));
+        assert!(unit(&simulation, hq).production_queue.is_empty());
+        assert_eq!(simulation.players[0].minerals, 2000);
+
+        // A finished Supply Depot makes room
+        simulation.spawn_building(BuildingType::SupplyDepot, 1000.0, 1000.0, 0);
+        simulation.tick(TICK_DT, &[]);
+        assert_eq!(simulation.players[0].supply_cap, 18);
+        simulation.tick(TICK_DT, &[train_worker]);
+        assert_eq!(unit(&simulation, hq).production_queue, [UnitType::Worker]);
+        assert_eq!(simulation.players[0].supply_used, 11);
+    }
+
+    #[test]
+    fn supply_never_goes_past_the_hard_maximum() {
+        let mut simulation = open_field();
+        for i in 0..20 {
+            simulation.spawn_building(BuildingType::SupplyDepot, 600.0 + (i % 10) as f32 * 80.0, 1000.0 + (i / 10) as f32 * 80.0, 0);
+        }
+        simulation.tick(TICK_DT, &[]);
+        assert_eq!(simulation.players[0].supply_cap, simulation.definitions.max_supply);
+    }
+}
@@ -0,0 +1,193 @@
+/// Side length of one spatial hash cell in world pixels.
+pub const SPATIAL_CELL_SIZE: f32 = 128.0;
+
+/// Uniform grid bucketing entries by position for fast proximity queries.
+///
+/// Entries are indices into whatever slice the grid was rebuilt from, so
+/// the grid must be rebuilt whenever that slice is reordered or shrinks.
+/// Positions outside the map are clamped into the edge cells.
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    pub cell_size: f32,
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<usize>>,
+    // Largest radius of any entry, so queries can pad for edges of big things
+    max_radius: f32,
+}
+
+impl SpatialHash {
+    pub fn new(map_width: f32, map_height: f32, cell_size: f32) -> Self {
+        let width = (map_width / cell_size).ceil().max(1.0) as usize;
+        let height = (map_height / cell_size).ceil().max(1.0) as usize;
+
+        Self {
+            cell_size,
+            width,
+            height,
+            cells: vec![Vec::new(); width * height],
+            max_radius: 0.0,
+        }
+    }
+
+    /// Replace the contents with `(x, y, radius)` entries, indexed by their
+    /// position in the iterator.
+    pub fn rebuild(&mut self, entries: impl Iterator<Item = (f32, f32, f32)>) {
+        for cell in &mut self.cells {
+            cell.clear();
+        }
+        self.max_radius = 0.0;
+
+        for (index, (x, y, radius)) in entries.enumerate() {
+            let (cx, cy) = self.cell_at(x, y);
+            self.cells[cy * self.width + cx].push(index);
+            self.max_radius = self.max_radius.max(radius);
+        }
+    }
+
+    pub fn max_radius(&self) -> f32 {
+        self.max_radius
+    }
+
+    fn cell_at(&self, x: f32, y: f32) -> (usize, usize) {
+        let cx = (x / self.cell_size).floor().clamp(0.0, (self.width - 1) as f32) as usize;
+        let cy = (y / self.cell_size).floor().clamp(0.0, (self.height - 1) as f32) as usize;
+        (cx, cy)
+    }
+
+    /// Every entry whose position may lie within `radius` of a point.
+    ///
+    /// This is a broad phase: callers still check the exact distance.
+    pub fn query(&self, x: f32, y: f32, radius: f32) -> Vec<usize> {
+        let (min_x, min_y) = self.cell_at(x - radius, y - radius);
+        let (max_x, max_y) = self.cell_at(x + radius, y + radius);
+        let mut result = Vec::new();
+
+        for cy in min_y..=max_y {
+            for cx in min_x..=max_x {
+                result.extend_from_slice(&self.cells[cy * self.width + cx]);
+            }
+        }
+
+        result
+    }
+
+    /// Closest entry within `max_range`, searching rings of cells outward.
+    ///
+    /// `distance_of` returns the distance to an entry, or `None` to skip it.
+    /// Ties go to whichever entry is visited first, which is stable for a
+    /// given grid, so results don't depend on anything but the world state.
+    pub fn nearest<F>(&self, x: f32, y: f32, max_range: f32, mut distance_of: F) -> Option<usize>
+    where
+        F: FnMut(usize) -> Option<f32>,
+    {
+        let (center_x, center_y) = self.cell_at(x, y);
+        let max_ring = self.width.max(self.height);
+        let range_rings = (max_range / self.cell_size).ceil().min(max_ring as f32) as usize + 1;
+
+        let mut best: Option<(usize, f32)> = None;
+
+        for ring in 0..=range_rings.min(max_ring) {
+            for cy in center_y.saturating_sub(ring)..=(center_y + ring).min(self.height - 1) {
+                for cx in center_x.saturating_sub(ring)..=(center_x + ring).min(self.width - 1) {
+                    // Only the outline of the ring; the inside was visited already
+                    if cx.abs_diff(center_x) != ring && cy.abs_diff(center_y) != ring {
+                        continue;
+                    }
+
+                    for &index in &self.cells[cy * self.width + cx] {
+                        if let Some(distance) = distance_of(index) {
+                            if distance < max_range && best.is_none_or(|(_, best_distance)| distance < best_distance) {
+                                best = Some((index, distance));
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Anything in a further ring is at least this far away
+            if let Some((_, best_distance)) = best {
+                if best_distance <= ring as f32 * self.cell_size {
+                    break;
+                }
+            }
+        }
+
+        best.map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::rng::SimRng;
+
+    // Entries scattered over a 1000x1000 map, a few just off its edges
+    fn scattered_entries(count: usize) -> Vec<(f32, f32, f32)> {
+        let mut rng = SimRng::new(7);
+        (0..count)
+            .map(|_| (rng.gen_range(-50.0, 1050.0), rng.gen_range(-50.0, 1050.0), rng.gen_range(5.0, 40.0)))
+            .collect()
+    }
+
+    fn distance(entry: (f32, f32, f32), x: f32, y: f32) -> f32 {
+        ((entry.0 - x).powi(2) + (entry.1 - y).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn query_finds_everything_a_linear_scan_does() {
+        let entries = scattered_entries(500);
+        let mut grid = SpatialHash::new(1000.0, 1000.0, SPATIAL_CELL_SIZE);
+        grid.rebuild(entries.iter().copied());
+
+        let mut rng = SimRng::new(11);
+        for _ in 0..200 {
+            let (x, y) = (rng.gen_range(0.0, 1000.0), rng.gen_range(0.0, 1000.0));
+            let radius = rng.gen_range(10.0, 300.0);
+
+            let mut found: Vec<usize> = grid.query(x, y, radius).into_iter()
+                .filter(|&index| distance(entries[index], x, y) <= radius)
+                .collect();
+            found.sort_unstable();
+            let expected: Vec<usize> = (0..entries.len())
+                .filter(|&index| distance(entries[index], x, y) <= radius)
+                .collect();
+            assert_eq!(found, expected, "query at ({}, {}) radius {}", x, y, radius);
+        }
+    }
+
+    #[test]
+    fn nearest_matches_a_linear_scan() {
+        let entries = scattered_entries(500);
+        let mut grid = SpatialHash::new(1000.0, 1000.0, SPATIAL_CELL_SIZE);
+        grid.rebuild(entries.iter().copied());
+        assert_eq!(grid.max_radius(), entries.iter().map(|entry| entry.2).fold(0.0, f32::max));
+
+        let mut rng = SimRng::new(13);
+        for _ in 0..200 {
+            let (x, y) = (rng.gen_range(0.0, 1000.0), rng.gen_range(0.0, 1000.0));
+            let max_range = rng.gen_range(10.0, 400.0);
+
+            let found = grid.nearest(x, y, max_range, |index| Some(distance(entries[index], x, y)));
+            let expected = (0..entries.len())
+                .filter(|&index| distance(entries[index], x, y) < max_range)
+                .min_by(|&a, &b| distance(entries[a], x, y).total_cmp(&distance(entries[b], x, y)));
+            assert_eq!(
+                found.map(|index| distance(entries[index], x, y)),
+                expected.map(|index| distance(entries[index], x, y)),
+                "nearest to ({}, {}) within {}", x, y, max_range
+            );
+        }
+    }
+
+    #[test]
+    fn rebuild_forgets_old_entries() {
+        let mut grid = SpatialHash::new(1000.0, 1000.0, SPATIAL_CELL_SIZE);
+        grid.rebuild([(100.0, 100.0, 50.0), (900.0, 900.0, 10.0)].into_iter());
+        grid.rebuild([(900.0, 900.0, 10.0)].into_iter());
+
+        assert!(grid.query(100.0, 100.0, 20.0).is_empty());
+        assert_eq!(grid.query(900.0, 900.0, 20.0), vec![0]);
+        assert_eq!(grid.max_radius(), 10.0);
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+
+/// Side length of one terrain tile in world pixels, matching the tile textures.
+pub const TILE_SIZE: f32 = 128.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TerrainType {
+    Grass,
+    Dirt,
+    Sand,
+    Rock,
+    Water,
+    RockObstacle,
+    Trees,
+}
+
+impl TerrainType {
+    /// Whether ground units can walk on this terrain.
+    pub fn is_passable(self) -> bool {
+        !matches!(self, TerrainType::Water | TerrainType::RockObstacle | TerrainType::Trees)
+    }
+
+    /// Multiplier applied to a unit's speed while it stands on this terrain.
+    pub fn speed_modifier(self) -> f32 {
+        match self {
+            TerrainType::Grass => 1.0,
+            TerrainType::Dirt => 1.1,
+            TerrainType::Sand => 0.7,
+            TerrainType::Rock => 0.85,
+            TerrainType::Water | TerrainType::RockObstacle | TerrainType::Trees => 0.0,
+        }
+    }
+
+    /// Character used for this terrain in map files.
+    pub fn to_char(self) -> char {
+        match self {
+            TerrainType::Grass => '.',
+            TerrainType::Dirt => 'd',
+            TerrainType::Sand => 's',
+            TerrainType::Rock => 'r',
+            TerrainType::Water => '~',
+            TerrainType::RockObstacle => 'R',
+            TerrainType::Trees => 'T',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(TerrainType::Grass),
+            'd' => Some(TerrainType::Dirt),
+            's' => Some(TerrainType::Sand),
+            'r' => Some(TerrainType::Rock),
+            '~' => Some(TerrainType::Water),
+            'R' => Some(TerrainType::RockObstacle),
+            'T' => Some(TerrainType::Trees),
+            _ => None,
+        }
+    }
+
+    /// Texture drawn for this terrain, by manifest name.
+    pub fn texture_name(self) -> &'static str {
+        match self {
+            TerrainType::Grass => "grass_tile",
+            TerrainType::Dirt => "dirt_tile",
+            TerrainType::Sand => "sand_tile",
+            TerrainType::Rock => "rock_tile",
+            TerrainType::Water => "water_tile",
+            TerrainType::RockObstacle => "obstacle_rock",
+            TerrainType::Trees => "obstacle_tree",
+        }
+    }
+}
+
+/// Terrain type of every tile on the map.
+///
+/// Positions outside the map count as impassable so nothing walks off it.
+#[derive(Debug, Clone)]
+pub struct TerrainMap {
+    pub tile_size: f32,
+    pub width: usize,
+    pub height: usize,
+    tiles: Vec<TerrainType>,
+}
+
+impl TerrainMap {
+    /// A map of plain grass.
+    pub fn new(map_width: f32, map_height: f32, tile_size: f32) -> Self {
+        let width = (map_width / tile_size).ceil().max(1.0) as usize;
+        let height = (map_height / tile_size).ceil().max(1.0) as usize;
+
+        Self {
+            tile_size,
+            width,
+            height,
+            tiles: vec![TerrainType::Grass; width * height],
+        }
+    }
+
+    /// Parse one string per row of tiles, as stored in map files.
+    pub fn from_rows(rows: &[String], tile_size: f32) -> Result<Self, String> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 || height == 0 {
+            return Err("terrain has no tiles".to_string());
+        }
+
+        let mut tiles = Vec::with_capacity(width * height);
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(format!("terrain row {} has {} tiles, expected {}", y, row.chars().count(), width));
+            }
+            for (x, c) in row.chars().enumerate() {
+                let terrain = TerrainType::from_char(c)
+                    .ok_or_else(|| format!("unknown terrain '{}' at tile ({}, {})", c, x, y))?;
+                tiles.push(terrain);
+            }
+        }
+
+        Ok(Self { tile_size, width, height, tiles })
+    }
+
+    /// One string per row of tiles, the inverse of `from_rows`.
+    pub fn to_rows(&self) -> Vec<String> {
+        self.tiles.chunks(self.width)
+            .map(|row| row.iter().map(|terrain| terrain.to_char()).collect())
+            .collect()
+    }
+
+    pub fn get(&self, tx: usize, ty: usize) -> Option<TerrainType> {
+        if tx < self.width && ty < self.height {
+            Some(self.tiles[ty * self.width + tx])
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, tx: usize, ty: usize, terrain: TerrainType) {
+        if tx < self.width && ty < self.height {
+            self.tiles[ty * self.width + tx] = terrain;
+        }
+    }
+
+    /// Tile containing a world position, or `None` off the map.
+    pub fn tile_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let tx = (x / self.tile_size) as usize;
+        let ty = (y / self.tile_size) as usize;
+        (tx < self.width && ty < self.height).then_some((tx, ty))
+    }
+
+    pub fn terrain_at(&self, x: f32, y: f32) -> Option<TerrainType> {
+        self.tile_at(x, y).and_then(|(tx, ty)| self.get(tx, ty))
+    }
+
+    pub fn is_passable(&self, x: f32, y: f32) -> bool {
+        self.terrain_at(x, y).is_some_and(TerrainType::is_passable)
+    }
+
+    pub fn speed_modifier_at(&self, x: f32, y: f32) -> f32 {
+        self.terrain_at(x, y).map_or(0.0, TerrainType::speed_modifier)
+    }
+
+    /// Set every tile whose center lies inside the circle.
+    pub fn paint_circle(&mut self, x: f32, y: f32, radius: f32, terrain: TerrainType) {
+        let min_x = ((x - radius) / self.tile_size).floor().max(0.0) as usize;
+        let min_y = ((y - radius) / self.tile_size).floor().max(0.0) as usize;
+        let max_x = ((x + radius) / self.tile_size).ceil().max(0.0) as usize;
+        let max_y = ((y + radius) / self.tile_size).ceil().max(0.0) as usize;
+
+        for ty in min_y..=max_y.min(self.height.saturating_sub(1)) {
+            for tx in min_x..=max_x.min(self.width.saturating_sub(1)) {
+                let center_x = (tx as f32 + 0.5) * self.tile_size;
+                let center_y = (ty as f32 + 0.5) * self.tile_size;
+                if (center_x - x).powi(2) + (center_y - y).powi(2) <= radius * radius {
+                    self.set(tx, ty, terrain);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&str]) -> Vec<String> {
+        rows.iter().map(|row| row.to_string()).collect()
+    }
+
+    #[test]
+    fn rows_round_trip() {
+        let terrain = rows(&[".dsr", "~RT.", "...."]);
+        let map = TerrainMap::from_rows(&terrain, 64.0).unwrap();
+        assert_eq!((map.width, map.height), (4, 3));
+        assert_eq!(map.get(1, 0), Some(TerrainType::Dirt));
+        assert_eq!(map.get(2, 1), Some(TerrainType::Trees));
+        assert_eq!(map.to_rows(), terrain);
+    }
+
+    #[test]
+    fn bad_rows_are_rejected() {
+        assert_eq!(TerrainMap::from_rows(&[], 64.0).unwrap_err(), "terrain has no tiles");
+        assert_eq!(TerrainMap::from_rows(&rows(&["...", ".."]), 64.0).unwrap_err(), "terrain row 1 has 2 tiles, expected 3");
+        assert_eq!(TerrainMap::from_rows(&rows(&["..x"]), 64.0).unwrap_err(), "unknown terrain 'x' at tile (2, 0)");
+    }
+
+    #[test]
+    fn passability_by_position() {
+        let map = TerrainMap::from_rows(&rows(&[".~", "RT"]), 64.0).unwrap();
+        assert!(map.is_passable(10.0, 10.0));
+        assert!(!map.is_passable(70.0, 10.0));
+        assert!(!map.is_passable(10.0, 70.0));
+        assert!(!map.is_passable(70.0, 70.0));
+        // Nothing walks off the map
+        assert!(!map.is_passable(-1.0, 10.0));
+        assert!(!map.is_passable(10.0, 128.0));
+    }
+
+    #[test]
+    fn speed_depends_on_ground() {
+        let map = TerrainMap::from_rows(&rows(&[".dsr~"]), 64.0).unwrap();
+        let speeds: Vec<f32> = (0..5).map(|tx| map.speed_modifier_at(tx as f32 * 64.0 + 32.0, 32.0)).collect();
+        assert_eq!(speeds, vec![1.0, 1.1, 0.7, 0.85, 0.0]);
+        assert_eq!(map.speed_modifier_at(400.0, 32.0), 0.0);
+    }
+
+    #[test]
+    fn paint_circle_covers_tile_centers_inside() {
+        let mut map = TerrainMap::new(320.0, 320.0, 64.0);
+        map.paint_circle(160.0, 160.0, 70.0, TerrainType::Water);
+        assert_eq!(map.to_rows(), rows(&[".....", "..~..", ".~~~.", "..~..", "....."]));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::entity::{BuildingType, Player, Unit, UnitType, UpgradeType};
+use crate::game::definitions::Definitions;
+use crate::game::resources::ResourceNode;
+use crate::game::projectiles::{Impact, Projectile};
+use crate::game::simulation::{overlaps_footprint, Simulation};
+use crate::game::terrain::TerrainMap;
+use crate::game::types::ResourceType;
+use crate::game::visibility::{RememberedBuilding, Visibility, VisibilityGrid};
+
+/// The world as one player is allowed to see it.
+///
+/// Their own units are always shown, enemy units only while in sight,
+/// and resource nodes once explored. Anything acting on behalf of a
+/// player (the AI, the renderer, network snapshots) reads the world
+/// through this rather than the simulation, so it can't peek through
+/// the fog.
+pub struct PlayerView<'a> {
+    simulation: &'a Simulation,
+    pub player_id: usize,
+}
+
+impl<'a> PlayerView<'a> {
+    pub fn new(simulation: &'a Simulation, player_id: usize) -> Self {
+        Self { simulation, player_id }
+    }
+
+    pub fn player(&self) -> Option<&'a Player> {
+        self.simulation.players.get(self.player_id)
+    }
+
+    /// The map layout is public knowledge.
+    pub fn terrain(&self) -> &'a TerrainMap {
+        &self.simulation.terrain
+    }
+
+    pub fn map_size(&self) -> (f32, f32) {
+        (self.simulation.map_width, self.simulation.map_height)
+    }
+
+    /// Every player's start location, as shown on the map before the match.
+    pub fn start_locations(&self) -> &'a [(f32, f32)] {
+        &self.simulation.start_locations
+    }
+
+    /// Unit and building stats are public knowledge too.
+    pub fn definitions(&self) -> &'a Definitions {
+        &self.simulation.definitions
+    }
+
+    pub fn game_time(&self) -> f32 {
+        self.simulation.game_time
+    }
+
+    pub fn visibility_grid(&self) -> Option<&'a VisibilityGrid> {
+        self.simulation.visibility.get(self.player_id)
+    }
+
+    pub fn visibility_at(&self, x: f32, y: f32) -> Visibility {
+        self.visibility_grid().map_or(Visibility::Unexplored, |grid| grid.at(x, y))
+    }
+
+    pub fn is_visible(&self, x: f32, y: f32) -> bool {
+        self.visibility_at(x, y) == Visibility::Visible
+    }
+
+    pub fn is_explored(&self, x: f32, y: f32) -> bool {
+        self.visibility_at(x, y) != Visibility::Unexplored
+    }
+
+    /// The player's own units plus every enemy unit currently in sight.
+    pub fn units(&self) -> impl Iterator<Item = &'a Unit> + '_ {
+        self.simulation.units.iter().filter(move |unit| self.can_see(unit))
+    }
+
+    pub fn own_units(&self) -> impl Iterator<Item = &'a Unit> + '_ {
+        self.simulation.units.iter().filter(move |unit| unit.player_id == self.player_id)
+    }
+
+    pub fn visible_enemies(&self) -> impl Iterator<Item = &'a Unit> + '_ {
+        self.units().filter(move |unit| unit.player_id != self.player_id)
+    }
+
+    /// Whether a unit is shown to this player.
+    pub fn can_see(&self, unit: &Unit) -> bool {
+        unit.player_id == self.player_id || self.is_visible(unit.x, unit.y)
+    }
+
+    /// Shots in flight where the player can see them.
+    pub fn projectiles(&self) -> impl Iterator<Item = &'a Projectile> + '_ {
+        self.simulation.projectiles.iter().filter(move |projectile| self.is_visible(projectile.x, projectile.y))
+    }
+
+    /// Recent impacts where the player can see them.
+    pub fn impacts(&self) -> impl Iterator<Item = &'a Impact> + '_ {
+        self.simulation.impacts.iter().filter(move |impact| self.is_visible(impact.x, impact.y))
+    }
+
+    /// Enemy buildings as last seen, including ones in sight right now.
+    pub fn known_enemy_buildings(&self) -> &'a [RememberedBuilding] {
+        self.visibility_grid().map_or(&[], |grid| &grid.remembered_buildings)
+    }
+
+    /// Resource nodes the player has explored, with their ids.
+    pub fn resource_nodes(&self) -> impl Iterator<Item = (usize, &'a ResourceNode)> + '_ {
+        self.simulation.resource_nodes.iter()
+            .filter(move |node| self.is_explored(node.x, node.y))
+            .map(|node| (node.id, node))
+    }
+
+    /// Whether the player can put a building at a spot as far as they know:
+    /// inside the map, on passable ground and clear of every unit, building,
+    /// resource node and object they can see or remember.
+    pub fn can_place_building(&self, building_type: &BuildingType, x: f32, y: f32) -> bool {
+        let radius = self.definitions().building(building_type).footprint;
+        self.simulation.footprint_on_open_ground(x, y, radius)
+            && !self.units().any(|unit| overlaps_footprint(x, y, radius, unit.x, unit.y, unit.collision_radius()))
+            && !self.known_enemy_buildings().iter().any(|building| overlaps_footprint(x, y, radius, building.x, building.y, building.footprint_radius()))
+            && !self.resource_nodes().any(|(_, node)| overlaps_footprint(x, y, radius, node.x, node.y, node.radius))
+            && !self.simulation.neutral_objects.iter().any(|object| overlaps_footprint(x, y, radius, object.x, object.y, object.radius))
+    }
+
+    pub fn find_headquarters(&self) -> Option<(f32, f32)> {
+        self.own_units()
+            .find(|unit| unit.unit_type == UnitType::Headquarters)
+            .map(|unit| (unit.x, unit.y))
+    }
+
+    /// Id of the closest explored resource node that still has resources,
+    /// only of `resource_type` if given.
+    pub fn find_nearest_resource(&self, x: f32, y: f32, resource_type: Option<&ResourceType>) -> Option<usize> {
+        self.simulation.resource_index
+            .nearest(x, y, f32::MAX, |i| {
+                let node = self.simulation.resource_nodes.get(i)?;
+                (node.resources > 0 && self.is_explored(node.x, node.y) && resource_type.is_none_or(|wanted| node.resource_type == *wanted))
+                    .then(|| ((node.x - x).powi(2) + (node.y - y).powi(2)).sqrt())
+            })
+            .map(|i| self.simulation.resource_nodes[i].id)
+    }
+
+    /// Position of the closest enemy in sight within `max_range`.
+    pub fn find_nearest_enemy(&self, x: f32, y: f32, max_range: f32) -> Option<(f32, f32)> {
+        self.simulation.unit_index
+            .nearest(x, y, max_range, |i| {
+                let unit = self.simulation.units.get(i)?;
+                (unit.player_id != self.player_id && unit.health > 0.0 && self.is_visible(unit.x, unit.y))
+                    .then(|| ((unit.x - x).powi(2) + (unit.y - y).powi(2)).sqrt())
+            })
+            .map(|i| (self.simulation.units[i].x, self.simulation.units[i].y))
+    }
+
+    /// An enemy in sight under a world position, if any.
+    pub fn enemy_at(&self, x: f32, y: f32) -> Option<u32> {
+        self.simulation.enemy_at(x, y, self.player_id)
+            .filter(|&id| self.simulation.units.iter().any(|unit| unit.id == id && self.can_see(unit)))
+    }
+
+    /// A resource node by id, once explored.
+    pub fn resource_node(&self, resource_id: usize) -> Option<&'a ResourceNode> {
+        self.simulation.resource_node(resource_id)
+            .filter(|node| self.is_explored(node.x, node.y))
+    }
+
+    /// Whether the player has a finished building of every type in `prerequisites`.
+    pub fn has_prerequisites(&self, prerequisites: &[BuildingType]) -> bool {
+        self.simulation.has_prerequisites(self.player_id, prerequisites)
+    }
+
+    pub fn can_afford(&self, unit_type: &UnitType) -> bool {
+        self.simulation.can_afford(self.player_id, unit_type)
+    }
+
+    pub fn has_supply(&self, unit_type: &UnitType) -> bool {
+        self.simulation.has_supply(self.player_id, unit_type)
+    }
+
+    pub fn can_research(&self, upgrade: &UpgradeType) -> bool {
+        self.simulation.can_research(self.player_id, upgrade)
+    }
+
+    /// Id of an explored resource node under a world position, if any.
+    pub fn resource_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.simulation.resource_at(x, y)
+            .filter(|&id| self.simulation.resource_node(id).is_some_and(|node| self.is_explored(node.x, node.y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::UnitType;
+    use crate::game::definitions::{Definitions, DEFINITIONS_PATH};
+    use crate::game::simulation::{Simulation, TICK_DT};
+
+    fn skirmish() -> Simulation {
+        Simulation::new(5, Definitions::load(DEFINITIONS_PATH).unwrap())
+    }
+
+    #[test]
+    fn enemies_show_only_while_in_sight() {
+        let mut simulation = skirmish();
+        let (enemy_x, enemy_y) = simulation.find_headquarters(1).unwrap();
+        let lurker = simulation.spawn_unit(UnitType::Fighter, enemy_x + 100.0, enemy_y, 1);
+        simulation.tick(TICK_DT, &[]);
+        assert!(!simulation.view(0).units().any(|unit| unit.id == lurker));
+        assert_eq!(simulation.view(0).visible_enemies().count(), 0);
+
+        simulation.spawn_unit(UnitType::Fighter, enemy_x + 200.0, enemy_y, 0);
+        simulation.tick(TICK_DT, &[]);
+        assert!(simulation.view(0).visible_enemies().any(|unit| unit.id == lurker));
+        assert!(simulation.view(1).own_units().any(|unit| unit.id == lurker));
+    }
+
+    #[test]
+    fn resource_nodes_stay_hidden_until_explored() {
+        let simulation = skirmish();
+        let (home_x, home_y) = simulation.find_headquarters(0).unwrap();
+        let (enemy_x, enemy_y) = simulation.find_headquarters(1).unwrap();
+        let home_node = simulation.find_nearest_resource(home_x, home_y, None).unwrap();
+        let enemy_node = simulation.find_nearest_resource(enemy_x, enemy_y, None).unwrap();
+
+        let view = simulation.view(0);
+        assert!(view.resource_node(home_node).is_some());
+        assert!(view.resource_node(enemy_node).is_none());
+        assert!(!view.resource_nodes().any(|(id, _)| id == enemy_node));
+        assert_ne!(view.find_nearest_resource(enemy_x, enemy_y, None), Some(enemy_node));
+    }
+
+    #[test]
+    fn buildings_are_remembered_after_losing_sight() {
+        let mut simulation = skirmish();
+        let (home_x, home_y) = simulation.find_headquarters(0).unwrap();
+        let (enemy_x, enemy_y) = simulation.find_headquarters(1).unwrap();
+        assert!(simulation.view(0).known_enemy_buildings().is_empty());
+
+        let scout = simulation.spawn_unit(UnitType::Fighter, enemy_x + 150.0, enemy_y, 0);
+        simulation.tick(TICK_DT, &[]);
+        let scout = simulation.units.iter_mut().find(|unit| unit.id == scout).unwrap();
+        (scout.x, scout.y) = (home_x + 100.0, home_y);
+        simulation.tick(TICK_DT, &[]);
+
+        let view = simulation.view(0);
+        assert!(!view.is_visible(enemy_x, enemy_y));
+        assert!(!view.units().any(|unit| unit.unit_type == UnitType::Headquarters && unit.player_id == 1));
+        assert!(view.known_enemy_buildings().iter().any(|building| {
+            building.unit_type == UnitType::Headquarters && building.x == enemy_x && building.y == enemy_y
+        }));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::entity::{BuildingType, Unit, UnitType};
+
+/// Side length of one visibility cell in world pixels.
+pub const VISIBILITY_CELL_SIZE: f32 = 64.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// Never seen; drawn black.
+    Unexplored,
+    /// Seen before but not in sight now; drawn dimmed.
+    Explored,
+    /// In sight of one of the player's units this tick.
+    Visible,
+}
+
+/// An enemy building as it looked the last time a player saw it.
+#[derive(Debug, Clone)]
+pub struct RememberedBuilding {
+    pub id: u32,
+    pub player_id: usize,
+    pub unit_type: UnitType,
+    pub building_type: Option<BuildingType>,
+    pub x: f32,
+    pub y: f32,
+    pub footprint: f32,
+}
+
+impl RememberedBuilding {
+    pub fn footprint_radius(&self) -> f32 {
+        self.footprint
+    }
+}
+
+/// What one player has explored and can currently see.
+///
+/// Positions outside the map count as unexplored.
+#[derive(Debug, Clone)]
+pub struct VisibilityGrid {
+    pub cell_size: f32,
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<Visibility>,
+    // Enemy buildings seen so far, kept until their spot is seen empty
+    pub remembered_buildings: Vec<RememberedBuilding>,
+}
+
+impl VisibilityGrid {
+    /// A grid with nothing explored yet.
+    pub fn new(map_width: f32, map_height: f32, cell_size: f32) -> Self {
+        let width = (map_width / cell_size).ceil().max(1.0) as usize;
+        let height = (map_height / cell_size).ceil().max(1.0) as usize;
+
+        Self {
+            cell_size,
+            width,
+            height,
+            cells: vec![Visibility::Unexplored; width * height],
+            remembered_buildings: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, cx: usize, cy: usize) -> Visibility {
+        if cx < self.width && cy < self.height {
+            self.cells[cy * self.width + cx]
+        } else {
+            Visibility::Unexplored
+        }
+    }
+
+    /// Visibility of the cell containing a world position.
+    pub fn at(&self, x: f32, y: f32) -> Visibility {
+        if x < 0.0 || y < 0.0 {
+            return Visibility::Unexplored;
+        }
+        self.get((x / self.cell_size) as usize, (y / self.cell_size) as usize)
+    }
+
+    pub fn is_visible(&self, x: f32, y: f32) -> bool {
+        self.at(x, y) == Visibility::Visible
+    }
+
+    pub fn is_explored(&self, x: f32, y: f32) -> bool {
+        self.at(x, y) != Visibility::Unexplored
+    }
+
+    /// Recompute what `player_id` sees from the sight of their units, and
+    /// update what they remember about enemy buildings.
+    pub fn update(&mut self, player_id: usize, units: &[Unit]) {
+        // Everything seen last tick is now only explored
+        for cell in &mut self.cells {
+            if *cell == Visibility::Visible {
+                *cell = Visibility::Explored;
+            }
+        }
+
+        for unit in units.iter().filter(|unit| unit.player_id == player_id) {
+            self.reveal_circle(unit.x, unit.y, unit.sight_radius());
+        }
+
+        // Forget buildings whose spot is in sight again but which are gone
+        let remembered = std::mem::take(&mut self.remembered_buildings);
+        self.remembered_buildings = remembered.into_iter()
+            .filter(|building| !self.is_visible(building.x, building.y) || units.iter().any(|u| u.id == building.id))
+            .collect();
+
+        for unit in units {
+            if unit.player_id == player_id || unit.footprint_radius() <= 0.0 || !self.is_visible(unit.x, unit.y) {
+                continue;
+            }
+
+            let snapshot = RememberedBuilding {
+                id: unit.id,
+                player_id: unit.player_id,
+                unit_type: unit.unit_type.clone(),
+                building_type: unit.building_type.clone(),
+                x: unit.x,
+                y: unit.y,
+                footprint: unit.footprint_radius(),
+            };
+            match self.remembered_buildings.iter_mut().find(|building| building.id == unit.id) {
+                Some(building) => *building = snapshot,
+                None => self.remembered_buildings.push(snapshot),
+            }
+        }
+    }
+
+    // Mark every cell whose center lies inside the circle as visible
+    fn reveal_circle(&mut self, x: f32, y: f32, radius: f32) {
+        let min_x = ((x - radius) / self.cell_size).floor().max(0.0) as usize;
+        let min_y = ((y - radius) / self.cell_size).floor().max(0.0) as usize;
+        let max_x = ((x + radius) / self.cell_size).ceil().max(0.0) as usize;
+        let max_y = ((y + radius) / self.cell_size).ceil().max(0.0) as usize;
+
+        for cy in min_y..=max_y.min(self.height - 1) {
+            for cx in min_x..=max_x.min(self.width - 1) {
+                let center_x = (cx as f32 + 0.5) * self.cell_size;
+                let center_y = (cy as f32 + 0.5) * self.cell_size;
+                if (center_x - x).powi(2) + (center_y - y).powi(2) <= radius * radius {
+                    self.cells[cy * self.width + cx] = Visibility::Visible;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod game;
+pub mod network;
+pub mod ui;
+pub mod ai;
+pub mod entity;
+pub mod resources;
+pub mod audio;
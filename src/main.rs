@@ -1,26 +1,50 @@
-mod game;
-mod network;
-mod ui;
-mod ai;
-mod entity;
-mod resources;
-mod audio;
-
 use macroquad::prelude::*;
-use crate::game::{GameMode, GameState};
-use crate::game::screens::GameScreen;
-use crate::ai::AIController;
-use crate::ui::menu::system::MenuSystem;
-use crate::resources::ResourceManager;
-use crate::network::NetworkClient;
-use crate::audio::AudioManager;
+use rust_rts::ui;
+use rust_rts::game::{GameMode, GameState};
+use rust_rts::game::screens::GameScreen;
+use rust_rts::game::map::MapData;
+use rust_rts::game::map_generator::MapGeneratorSettings;
+use rust_rts::game::definitions::{Definitions, DEFINITIONS_PATH};
+use rust_rts::ai::AIController;
+use rust_rts::ui::menu::system::MenuSystem;
+use rust_rts::resources::ResourceManager;
+use rust_rts::network::NetworkClient;
+use rust_rts::audio::AudioManager;
 use std::panic::{self, AssertUnwindSafe}; // Add this import for AssertUnwindSafe
 
 #[macroquad::main("Fantasy RTS")]
 async fn main() {
-    let mut game_state = GameState::new();
+    // Unit and building stats; there's no game without them
+    let definitions = match Definitions::load(DEFINITIONS_PATH) {
+        Ok(definitions) => definitions,
+        Err(e) => {
+            eprintln!("Failed to load definitions {}: {}", DEFINITIONS_PATH, e);
+            return;
+        }
+    };
+
+    // `--seed <n>` replays a match; a fresh one is picked otherwise
+    let seed = parsed_argument("--seed").unwrap_or_else(GameState::match_seed);
+
+    // A map can be picked on the command line, e.g. `--map assets/maps/crossroads.json`,
+    // and is generated otherwise
+    let settings = generator_settings();
+    let mut game_state = match argument("--map") {
+        Some(path) => match MapData::load(&path) {
+            Ok(map) => GameState::from_map(&map, seed, definitions),
+            Err(e) => {
+                eprintln!("Failed to load map {}: {}", path, e);
+                GameState::new(&settings, seed, definitions)
+            }
+        },
+        None => GameState::new(&settings, seed, definitions),
+    };
     let mut network_client = NetworkClient::new();
-    let mut ai_controller = AIController::new();
+    // One controller per computer player, each thinking with its own seed
+    let mut ai_controllers: Vec<AIController> = game_state.simulation.players.iter()
+        .filter(|player| player.is_ai)
+        .map(|player| AIController::new(player.id, seed.wrapping_add(player.id as u64)))
+        .collect();
     let mut menu_system = MenuSystem::new();
     let mut resource_manager = ResourceManager::new();
     let mut audio_manager = AudioManager::new();
@@ -116,18 +140,19 @@ async fn main() {
                     
                     // Game update logic
                     let previous_selected = game_state.selected_units.clone();
-                    game_state.update();
+                    // Process AI in offline mode
+                    let ai: &mut [AIController] = if game_state.game_mode == GameMode::Offline {
+                        &mut ai_controllers
+                    } else {
+                        &mut []
+                    };
+                    game_state.update(ai);
                     
                     // Play selection sound if selection changed
                     if previous_selected != game_state.selected_units && !game_state.selected_units.is_empty() {
                         audio_manager.play_selection_sound(&resource_manager, &game_state);
                     }
                     
-                    // Process AI in offline mode
-                    if game_state.game_mode == GameMode::Offline {
-                        ai_controller.update(&mut game_state);
-                    }
-                    
                     // Handle networking only if in online mode
                     if game_state.game_mode == GameMode::Online && network_client.is_connected() {
                         if let Some(msg) = network_client.receive() {
@@ -165,3 +190,54 @@ async fn main() {
         next_frame().await;
     }
 }
+
+// Value given with `<name> <value>` on the command line, if any
+fn argument(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+    None
+}
+
+// A command line value parsed, ignoring it with a warning if it doesn't parse
+fn parsed_argument<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = argument(name)?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            eprintln!("Ignoring {} {}: not a valid value", name, value);
+            None
+        }
+    }
+}
+
+// Generated map shape from `--players`, `--map-size <width>x<height>`,
+// `--terrain-density`, `--resource-density` and `--expansions`
+fn generator_settings() -> MapGeneratorSettings {
+    let mut settings = MapGeneratorSettings::default();
+    if let Some(players) = parsed_argument("--players") {
+        settings.players = players;
+    }
+    if let Some(size) = argument("--map-size") {
+        match size.split_once('x').and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?))) {
+            Some((width, height)) => {
+                settings.width = width;
+                settings.height = height;
+            },
+            None => eprintln!("Ignoring --map-size {}: expected <width>x<height>", size),
+        }
+    }
+    if let Some(density) = parsed_argument("--terrain-density") {
+        settings.terrain_density = density;
+    }
+    if let Some(density) = parsed_argument("--resource-density") {
+        settings.resource_density = density;
+    }
+    if let Some(expansions) = parsed_argument("--expansions") {
+        settings.expansions_per_player = expansions;
+    }
+    settings
+}
@@ -150,3 +150,9 @@ impl NetworkClient {
         self.is_connected
     }
 }
+
+impl Default for NetworkClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use crate::entity::Unit;
+use crate::game::view::PlayerView;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum NetworkMessage {
@@ -24,3 +25,15 @@ pub enum NetworkMessage {
         message: String,
     },
 }
+
+impl NetworkMessage {
+    /// A state snapshot for one player holding only the units their view
+    /// shows. Build `GameState` messages through this so nobody is sent
+    /// units hidden by their fog of war.
+    pub fn game_state_for(view: &PlayerView) -> Self {
+        NetworkMessage::GameState {
+            units: view.units().cloned().collect(),
+            timestamp: view.game_time() as f64,
+        }
+    }
+}
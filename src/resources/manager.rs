@@ -273,3 +273,9 @@ impl ResourceManager {
         Ok(())
     }
 }
+
+impl Default for ResourceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -4,8 +4,12 @@ use crate::game::screens::GameScreen;
 use crate::network::NetworkClient; // Fixed import
 use crate::resources::manager::ResourceManager;
 use crate::audio::AudioManager;
-use crate::entity::{BuildingType, UnitType};
-use crate::game::commands::Command;
+use crate::entity::{BuildingType, Stance, Unit, UnitType, UpgradeType};
+use crate::game::definitions::{Definitions, BUILDING_TYPES, TRAINABLE_UNIT_TYPES, UPGRADE_TYPES};
+use crate::game::simulation::PRODUCTION_QUEUE_LIMIT;
+use crate::game::commands::{Command, PlayerCommand};
+use crate::game::rendering;
+use crate::game::state::Targeting;
 
 pub fn draw_ui(
     game_state: &mut GameState,
@@ -17,7 +21,7 @@ pub fn draw_ui(
     let bar_height = 50.0;
     draw_rectangle(0.0, 0.0, screen_width(), bar_height, Color::new(0.1, 0.1, 0.3, 0.8));
     
-    if let Some(player) = game_state.players.get(game_state.current_player_id as usize) {
+    if let Some(player) = game_state.simulation.players.get(game_state.current_player_id) {
         // Draw minerals
         let minerals_text = format!("Minerals: {}", player.minerals);
         draw_text(&minerals_text, 20.0, 30.0, 20.0, WHITE);
@@ -25,6 +29,11 @@ pub fn draw_ui(
         // Draw energy
         let energy_text = format!("Energy: {}", player.energy);
         draw_text(&energy_text, 200.0, 30.0, 20.0, WHITE);
+        
+        // Draw supply, in red once it's all used up
+        let supply_text = format!("Supply: {}/{}", player.supply_used, player.supply_cap);
+        let supply_color = if player.supply_used >= player.supply_cap { RED } else { WHITE };
+        draw_text(&supply_text, 380.0, 30.0, 20.0, supply_color);
     }
     
     // Draw mini-map
@@ -36,24 +45,28 @@ pub fn draw_ui(
         Color::new(0.1, 0.3, 0.1, 0.8)
     );
     
+    // Only what the local player has seen shows up
+    let view = game_state.simulation.view(game_state.current_player_id);
+    rendering::draw_minimap_fog(&view, &game_state.minimap_rect);
+    
     // Draw units on minimap
-    for unit in &game_state.units {
-        let map_ratio_x = game_state.minimap_rect.w / game_state.map_width;
-        let map_ratio_y = game_state.minimap_rect.h / game_state.map_height;
+    for unit in view.units() {
+        let map_ratio_x = game_state.minimap_rect.w / game_state.simulation.map_width;
+        let map_ratio_y = game_state.minimap_rect.h / game_state.simulation.map_height;
         
         let mini_x = game_state.minimap_rect.x + unit.x * map_ratio_x;
         let mini_y = game_state.minimap_rect.y + unit.y * map_ratio_y;
         
-        let color = game_state.players[unit.player_id as usize].color;
+        let color = game_state.simulation.players[unit.player_id].color;
         
         draw_circle(mini_x, mini_y, 2.0, color);
     }
     
     // Draw camera viewport on minimap
-    let view_x = game_state.minimap_rect.x + game_state.camera_x * game_state.minimap_rect.w / game_state.map_width;
-    let view_y = game_state.minimap_rect.y + game_state.camera_y * game_state.minimap_rect.h / game_state.map_height;
-    let view_w = screen_width() * game_state.minimap_rect.w / game_state.map_width;
-    let view_h = screen_height() * game_state.minimap_rect.h / game_state.map_height;
+    let view_x = game_state.minimap_rect.x + game_state.camera_x * game_state.minimap_rect.w / game_state.simulation.map_width;
+    let view_y = game_state.minimap_rect.y + game_state.camera_y * game_state.minimap_rect.h / game_state.simulation.map_height;
+    let view_w = screen_width() * game_state.minimap_rect.w / game_state.simulation.map_width;
+    let view_h = screen_height() * game_state.minimap_rect.h / game_state.simulation.map_height;
     
     draw_rectangle_lines(view_x, view_y, view_w, view_h, 1.0, WHITE);
     
@@ -70,8 +83,8 @@ pub fn draw_ui(
     }
     
     // Draw game time
-    let minutes = (game_state.game_time / 60.0) as i32;
-    let seconds = (game_state.game_time % 60.0) as i32;
+    let minutes = (game_state.simulation.game_time / 60.0) as i32;
+    let seconds = (game_state.simulation.game_time % 60.0) as i32;
     let time_text = format!("Time: {:02}:{:02}", minutes, seconds);
     draw_text(&time_text, screen_width() - 120.0, 30.0, 20.0, WHITE);
     
@@ -98,41 +111,55 @@ pub fn draw_ui(
     draw_text("Game UI", 10.0, screen_height() - 30.0, 20.0, WHITE);
     
     // Show controls
-    draw_text("Controls: WASD to move camera, Mouse to select units", 10.0, screen_height() - 60.0, 16.0, LIGHTGRAY);
+    draw_text("Controls: WASD to move camera, Mouse to select units, A+click to attack-move, Shift+right-click to queue orders", 10.0, screen_height() - 60.0, 16.0, LIGHTGRAY);
+    
+    // Remind the player an order is waiting for its target
+    if game_state.targeting.is_some() {
+        draw_text("Left-click to choose a target (Esc or right-click to cancel)", 10.0, screen_height() - 80.0, 16.0, YELLOW);
+    }
     
     egui_macroquad::ui(|egui_ctx| {
         // Command Panel
         egui_macroquad::egui::Window::new("Commands").show(egui_ctx, |ui| {
             if !game_state.selected_units.is_empty() {
-                if ui.button("Build Barracks").clicked() {
-                    audio_manager.play_ui_click(resource_manager, game_state);
-                    game_state.current_command = Some(Command::Build { 
-                        building_type: BuildingType::Barracks,
-                        x: 0.0,
-                        y: 0.0 
-                    });
+                for building_type in BUILDING_TYPES {
+                    // Locked until the player has finished what it needs
+                    let definition = game_state.simulation.definitions.building(&building_type);
+                    let label = format!("Build {} ({})", building_name(&building_type), definition.cost);
+                    let missing = game_state.simulation.missing_prerequisites(game_state.current_player_id, &definition.prerequisites);
+                    let button = egui_macroquad::egui::Button::new(label);
+                    if ui.add_enabled(missing.is_empty(), button).on_disabled_hover_text(requirements_text(&missing)).clicked() {
+                        audio_manager.play_ui_click(resource_manager, game_state);
+                        game_state.targeting = Some(Targeting::Build(building_type));
+                    }
                 }
                 if ui.button("Gather Resources").clicked() {
                     audio_manager.play_ui_click(resource_manager, game_state);
-                    game_state.current_command = Some(Command::Gather { resource_id: 0 });
+                    game_state.targeting = Some(Targeting::Gather);
+                }
+                if ui.button("Patrol").clicked() {
+                    audio_manager.play_ui_click(resource_manager, game_state);
+                    game_state.targeting = Some(Targeting::Patrol);
                 }
             }
         });
 
         // Unit Training Panel
         egui_macroquad::egui::Window::new("Train Units").show(egui_ctx, |ui| {
-            if ui.button("Train Worker (50 minerals)").clicked() && game_state.can_afford(0, &UnitType::Worker) {
+            let worker_cost = game_state.simulation.definitions.unit(&UnitType::Worker).cost;
+            let button = egui_macroquad::egui::Button::new(format!("Train Worker ({} minerals)", worker_cost.minerals));
+            let has_supply = game_state.simulation.has_supply(game_state.current_player_id, &UnitType::Worker);
+            if ui.add_enabled(has_supply, button).on_disabled_hover_text(supply_warning(game_state)).clicked() &&
+               game_state.simulation.can_afford(game_state.current_player_id, &UnitType::Worker) {
                 audio_manager.play_build_sound(resource_manager, game_state);
                 
-                // Find HQ position
-                if let Some(headquarters) = game_state.units.iter().find(|u| 
-                    u.unit_type == UnitType::Headquarters && u.player_id == game_state.current_player_id) {
-                    let hq_pos = (headquarters.x, headquarters.y);
-                    let spawn_pos = (hq_pos.0 + 50.0, hq_pos.1 + 50.0);
-                    
-                    // Create worker and deduct cost
-                    game_state.spawn_unit(UnitType::Worker, spawn_pos.0, spawn_pos.1, game_state.current_player_id);
-                    game_state.deduct_cost(game_state.current_player_id as usize, &UnitType::Worker);
+                // Train from the player's HQ
+                if let Some(headquarters_id) = game_state.own_headquarters() {
+                    game_state.pending_commands.push(PlayerCommand::new(
+                        game_state.current_player_id,
+                        vec![headquarters_id],
+                        Command::Train { unit_type: UnitType::Worker },
+                    ));
                 }
             }
         });
@@ -142,14 +169,37 @@ pub fn draw_ui(
             if !game_state.selected_units.is_empty() {
                 if ui.button("Attack").clicked() {
                     audio_manager.play_ui_click(resource_manager, game_state);
-                    game_state.current_command = Some(Command::Attack { target_id: 0 });
+                    game_state.targeting = Some(Targeting::Attack);
                 }
                 if ui.button("Stop").clicked() {
                     audio_manager.play_ui_click(resource_manager, game_state);
-                    game_state.current_command = Some(Command::Stop);
+                    game_state.issue_command(Command::Stop);
                 }
+                if ui.button("Guard").clicked() {
+                    audio_manager.play_ui_click(resource_manager, game_state);
+                    game_state.targeting = Some(Targeting::Guard);
+                }
+                
+                // Stance of the whole selection
+                ui.separator();
+                ui.horizontal(|ui| {
+                    for (label, stance) in [
+                        ("Aggressive", Stance::Aggressive),
+                        ("Defensive", Stance::Defensive),
+                        ("Hold", Stance::HoldPosition),
+                        ("Passive", Stance::Passive),
+                    ] {
+                        if ui.button(label).clicked() {
+                            audio_manager.play_ui_click(resource_manager, game_state);
+                            game_state.issue_command(Command::SetStance { stance });
+                        }
+                    }
+                });
             }
         });
+        
+        // Keep clicks on these windows from reaching the world next frame
+        game_state.ui_wants_pointer = egui_ctx.wants_pointer_input() || egui_ctx.is_pointer_over_area();
     });
 }
 
@@ -158,30 +208,40 @@ fn draw_unit_info(
     resource_manager: &ResourceManager,
     audio_manager: &mut AudioManager
 ) {
-    let panel_height = 150.0;
-    let panel_y = screen_height() - panel_height;
+    let panel = match game_state.unit_panel_rect() {
+        Some(panel) => panel,
+        None => return,
+    };
+    let panel_y = panel.y;
     
     // Draw panel background
-    draw_rectangle(0.0, panel_y, screen_width() - game_state.minimap_rect.w - 10.0, panel_height, Color::new(0.1, 0.1, 0.3, 0.8));
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.3, 0.8));
     
     // Find first selected unit to show info
     if let Some(selected_id) = game_state.selected_units.first() {
-        if let Some(unit) = game_state.units.iter().find(|u| u.id == *selected_id) {
+        if let Some(unit) = game_state.simulation.units.iter().find(|u| u.id == *selected_id).cloned() {
             // Draw unit name and info
-            let unit_type_str = match unit.unit_type {
-                UnitType::Worker => "Worker",
-                UnitType::Fighter => "Fighter",
-                UnitType::Ranger => "Ranger",
-                UnitType::Tank => "Tank",
-                UnitType::Building => "Building",
-                UnitType::Headquarters => "Headquarters",
-            };
-            
-            draw_text(&format!("{} (ID: {})", unit_type_str, unit.id), 20.0, panel_y + 30.0, 20.0, WHITE);
+            draw_text(&format!("{} (ID: {})", unit_name(&unit), unit.id), 20.0, panel_y + 30.0, 20.0, WHITE);
+            draw_combat_stats(game_state, &unit, 480.0, panel_y);
             draw_text(&format!("Health: {}/{}", unit.health, unit.max_health), 20.0, panel_y + 60.0, 16.0, WHITE);
+            if let Some(progress) = unit.construction_progress {
+                draw_text(&format!("Under construction: {:.0}%", progress * 100.0), 200.0, panel_y + 60.0, 16.0, ORANGE);
+            } else if let Some(upgrade) = &unit.research {
+                let research_time = game_state.simulation.definitions.upgrade(upgrade).research_time;
+                let progress = (unit.research_progress / research_time).min(1.0);
+                draw_text(&format!("Researching {}: {:.0}%", upgrade_name(upgrade), progress * 100.0), 200.0, panel_y + 60.0, 16.0, SKYBLUE);
+            } else if unit.speed > 0.0 {
+                let stance_str = match unit.stance {
+                    Stance::Aggressive => "Aggressive",
+                    Stance::Defensive => "Defensive",
+                    Stance::HoldPosition => "Hold Position",
+                    Stance::Passive => "Passive",
+                };
+                draw_text(&format!("Stance: {}", stance_str), 200.0, panel_y + 60.0, 16.0, WHITE);
+            }
             
             // Draw action buttons based on unit type
-            let button_width = 120.0;
+            let button_width = 150.0;
             let button_height = 40.0;
             let button_spacing = 10.0;
             let button_start_x = 20.0;
@@ -189,59 +249,136 @@ fn draw_unit_info(
             
             match unit.unit_type {
                 UnitType::Worker => {
-                    if draw_button(button_start_x, button_y, button_width, button_height, "Build") {
+                    if draw_button(button_start_x, button_y, button_width, button_height, "Gather") {
                         audio_manager.play_ui_click(resource_manager, game_state);
-                        game_state.current_command = Some(Command::Build {
-                            building_type: BuildingType::Barracks,
-                            x: 0.0,
-                            y: 0.0
-                        });
+                        game_state.targeting = Some(Targeting::Gather);
                     }
                     
-                    if draw_button(button_start_x + button_width + button_spacing, button_y, button_width, button_height, "Gather") {
-                        audio_manager.play_ui_click(resource_manager, game_state);
-                        game_state.current_command = Some(Command::Gather { resource_id: 0 });
+                    // One button per building; locked ones are grayed out and say what they need
+                    let build_width = 130.0;
+                    let build_start_x = button_start_x + button_width + button_spacing;
+                    for (i, building_type) in BUILDING_TYPES.iter().enumerate() {
+                        let definition = game_state.simulation.definitions.building(building_type);
+                        let missing = game_state.simulation.missing_prerequisites(game_state.current_player_id, &definition.prerequisites);
+                        let player = &game_state.simulation.players[game_state.current_player_id];
+                        let affordable = player.minerals >= definition.cost.minerals && player.energy >= definition.cost.energy;
+                        let tooltip = if missing.is_empty() { format!("Costs {}", definition.cost) } else { requirements_text(&missing) };
+                        
+                        let x = build_start_x + i as f32 * (build_width + button_spacing);
+                        let color = if missing.is_empty() && affordable { SKYBLUE } else { GRAY };
+                        if draw_button_colored(x, button_y, build_width, button_height, building_name(building_type), color) && missing.is_empty() {
+                            audio_manager.play_ui_click(resource_manager, game_state);
+                            game_state.targeting = Some(Targeting::Build(building_type.clone()));
+                        }
+                        draw_tooltip(x, button_y, build_width, button_height, &tooltip);
                     }
                 },
-                UnitType::Headquarters => {
-                    let player = &game_state.players[game_state.current_player_id];
+                UnitType::Building if unit.is_complete() && unit.building_type == Some(BuildingType::ResearchCenter) => {
+                    let player_id = game_state.current_player_id;
+                    let research_width = 200.0;
                     
-                    // Highlight button if can afford, gray out if cannot
-                    let can_afford_worker = player.minerals >= 50;
-                    if draw_button_colored(
-                        button_start_x, 
-                        button_y, 
-                        button_width, 
-                        button_height, 
-                        "Train Worker (50)", 
-                        if can_afford_worker { SKYBLUE } else { GRAY }
-                    ) && can_afford_worker {
-                        audio_manager.play_build_sound(resource_manager, game_state);
+                    for (i, upgrade) in UPGRADE_TYPES.iter().enumerate() {
+                        let definition = game_state.simulation.definitions.upgrade(upgrade);
+                        let missing = game_state.simulation.missing_prerequisites(player_id, &definition.prerequisites);
+                        let done = game_state.simulation.players[player_id].upgrades.contains(upgrade);
+                        let label = if done {
+                            format!("{} (done)", upgrade_name(upgrade))
+                        } else {
+                            format!("{} ({})", upgrade_name(upgrade), definition.cost)
+                        };
                         
-                        // Find position near HQ
-                        let hq_pos = (unit.x, unit.y);
-                        let spawn_pos = (hq_pos.0 + 50.0, hq_pos.1 + 50.0);
+                        // One upgrade at a time per Research Center, and each only once
+                        let available = unit.research.is_none() && game_state.simulation.can_research(player_id, upgrade);
+                        let color = if done { DARKGREEN } else if available { SKYBLUE } else { GRAY };
+                        let x = button_start_x + i as f32 * (research_width + button_spacing);
+                        if draw_button_colored(x, button_y, research_width, button_height, &label, color) && available {
+                            audio_manager.play_build_sound(resource_manager, game_state);
+                            game_state.issue_command(Command::Research { upgrade: upgrade.clone() });
+                        }
+                        if !done && !missing.is_empty() {
+                            draw_tooltip(x, button_y, research_width, button_height, &requirements_text(&missing));
+                        }
+                    }
+                    
+                    // Cancelling gives back everything it cost
+                    if unit.research.is_some() {
+                        let x = button_start_x + UPGRADE_TYPES.len() as f32 * (research_width + button_spacing);
+                        if draw_button(x, button_y, button_width, button_height, "Cancel Research") {
+                            audio_manager.play_ui_click(resource_manager, game_state);
+                            game_state.pending_commands.push(PlayerCommand::new(
+                                game_state.current_player_id,
+                                vec![unit.id],
+                                Command::CancelResearch,
+                            ));
+                        }
+                    }
+                },
+                UnitType::Headquarters | UnitType::Building if unit.is_complete() => {
+                    let trainable: Vec<UnitType> = TRAINABLE_UNIT_TYPES
+                        .into_iter()
+                        .filter(|unit_type| game_state.simulation.definitions.can_train(&unit, unit_type))
+                        .collect();
+                    let queue_full = unit.production_queue.len() >= PRODUCTION_QUEUE_LIMIT;
+                    
+                    // Warn when supply is what's holding training back
+                    let supply_blocked = trainable.iter().any(|unit_type| !game_state.simulation.has_supply(game_state.current_player_id, unit_type));
+                    if supply_blocked {
+                        draw_text(supply_warning(game_state), 200.0, panel_y + 60.0, 16.0, RED);
+                    }
+                    
+                    for (i, unit_type) in trainable.iter().enumerate() {
+                        let definition = game_state.simulation.definitions.unit(unit_type);
+                        let label = format!("Train {:?} ({})", unit_type, definition.cost);
+                        let missing = game_state.simulation.missing_prerequisites(game_state.current_player_id, &definition.prerequisites);
+                        let has_supply = game_state.simulation.has_supply(game_state.current_player_id, unit_type);
                         
-                        // Create worker and deduct cost
-                        game_state.spawn_unit(UnitType::Worker, spawn_pos.0, spawn_pos.1, game_state.current_player_id);
-                        game_state.deduct_cost(game_state.current_player_id as usize, &UnitType::Worker);
+                        // Highlight button if unlocked, affordable and in supply, gray out if not
+                        let available = !queue_full && missing.is_empty() && has_supply && game_state.simulation.can_afford(game_state.current_player_id, unit_type);
+                        let x = button_start_x + i as f32 * (button_width + button_spacing);
+                        if draw_button_colored(x, button_y, button_width, button_height, &label, if available { SKYBLUE } else { GRAY })
+                            && available
+                        {
+                            audio_manager.play_build_sound(resource_manager, game_state);
+                            // Shared out over every selected building that trains it
+                            game_state.issue_command(Command::Train { unit_type: unit_type.clone() });
+                        }
+                        if !missing.is_empty() {
+                            draw_tooltip(x, button_y, button_width, button_height, &requirements_text(&missing));
+                        } else if !has_supply {
+                            draw_tooltip(x, button_y, button_width, button_height, supply_warning(game_state));
+                        }
+                    }
+                    
+                    // The queue, front first; clicking an entry cancels it
+                    if !trainable.is_empty() {
+                        let queue_x = button_start_x + trainable.len() as f32 * (button_width + button_spacing) + button_spacing;
+                        if let Some(slot) = draw_production_queue(&unit, &game_state.simulation.definitions, queue_x, button_y) {
+                            audio_manager.play_ui_click(resource_manager, game_state);
+                            game_state.pending_commands.push(PlayerCommand::new(
+                                game_state.current_player_id,
+                                vec![unit.id],
+                                Command::CancelTraining { slot },
+                            ));
+                        }
+                    }
+                },
+                // Cancelling gives back most of what the foundation cost
+                UnitType::Building if !unit.is_complete() => {
+                    if draw_button(button_start_x, button_y, button_width, button_height, "Cancel") {
+                        audio_manager.play_ui_click(resource_manager, game_state);
+                        game_state.issue_command(Command::CancelConstruction);
                     }
                 },
                 _ => {
                     if draw_button(button_start_x, button_y, button_width, button_height, "Attack") {
                         audio_manager.play_ui_click(resource_manager, game_state);
-                        game_state.current_command = Some(Command::Attack { target_id: 0 });
+                        game_state.targeting = Some(Targeting::Attack);
                     }
                     
                     if draw_button(button_start_x + button_width + button_spacing, button_y, button_width, button_height, "Stop") {
                         audio_manager.play_ui_click(resource_manager, game_state);
                         // Clear target for all selected units
-                        for &unit_id in &game_state.selected_units {
-                            if let Some(unit) = game_state.units.iter_mut().find(|u| u.id == unit_id) {
-                                unit.target_x = None;
-                                unit.target_y = None;
-                            }
-                        }
+                        game_state.issue_command(Command::Stop);
                     }
                 }
             }
@@ -260,6 +397,34 @@ fn draw_unit_info(
     }
 }
 
+// A box per production queue slot with a progress bar under the one in
+// training; returns the slot clicked this frame
+fn draw_production_queue(producer: &Unit, definitions: &Definitions, x: f32, y: f32) -> Option<usize> {
+    let slot_size = 40.0;
+    let mut clicked = None;
+    
+    for slot in 0..PRODUCTION_QUEUE_LIMIT {
+        let slot_x = x + slot as f32 * (slot_size + 4.0);
+        match producer.production_queue.get(slot) {
+            Some(unit_type) => {
+                let label = format!("{:?}", unit_type).chars().next().unwrap_or('?').to_string();
+                if draw_button_colored(slot_x, y, slot_size, slot_size, &label, DARKBLUE) {
+                    clicked = Some(slot);
+                }
+            },
+            None => draw_rectangle_lines(slot_x, y, slot_size, slot_size, 1.0, GRAY),
+        }
+    }
+    
+    if let Some(unit_type) = producer.production_queue.front() {
+        let progress = (producer.production_progress / definitions.unit(unit_type).build_time).min(1.0);
+        draw_rectangle(x, y + slot_size + 4.0, slot_size, 5.0, DARKGRAY);
+        draw_rectangle(x, y + slot_size + 4.0, slot_size * progress, 5.0, GREEN);
+    }
+    
+    clicked
+}
+
 fn draw_button(x: f32, y: f32, width: f32, height: f32, text: &str) -> bool {
     draw_button_colored(x, y, width, height, text, SKYBLUE)
 }
@@ -283,6 +448,95 @@ fn draw_button_colored(x: f32, y: f32, width: f32, height: f32, text: &str, colo
     hover && is_mouse_button_released(MouseButton::Left)
 }
 
+// Armor and damage, and the damage per second against the unit it's locked
+// on to or the visible enemy under the mouse
+fn draw_combat_stats(game_state: &GameState, unit: &Unit, x: f32, panel_y: f32) {
+    let mut stats = format!("Armor: {} {:?}", unit.armor, unit.armor_type);
+    if unit.attack_damage > 0.0 {
+        stats = format!("Damage: {} {:?}   {}", unit.attack_damage, unit.damage_type, stats);
+    }
+    draw_text(&stats, x, panel_y + 30.0, 16.0, LIGHTGRAY);
+    if unit.attack_damage <= 0.0 {
+        return;
+    }
+    
+    let view = game_state.simulation.view(game_state.current_player_id);
+    let (mouse_x, mouse_y) = mouse_position();
+    let target_id = unit.attack_target
+        .or_else(|| view.enemy_at(mouse_x + game_state.camera_x, mouse_y + game_state.camera_y));
+    let target = target_id
+        .and_then(|id| game_state.simulation.units.iter().find(|u| u.id == id))
+        .filter(|target| target.player_id != unit.player_id && view.can_see(target));
+    
+    if let Some(target) = target {
+        let dps = game_state.simulation.definitions.dps_against(unit, target);
+        draw_text(&format!("DPS vs {}: {:.1}", unit_name(target), dps), x, panel_y + 60.0, 16.0, ORANGE);
+    }
+}
+
+fn unit_name(unit: &Unit) -> &'static str {
+    match (&unit.unit_type, &unit.building_type) {
+        (UnitType::Worker, _) => "Worker",
+        (UnitType::Fighter, _) => "Fighter",
+        (UnitType::Ranger, _) => "Ranger",
+        (UnitType::Tank, _) => "Tank",
+        (UnitType::Building, Some(building_type)) => building_name(building_type),
+        (UnitType::Building, None) => "Building",
+        (UnitType::Headquarters, _) => "Headquarters",
+    }
+}
+
+// A box with `text` above a button while the mouse is over it
+fn draw_tooltip(x: f32, y: f32, width: f32, height: f32, text: &str) {
+    let (mouse_x, mouse_y) = mouse_position();
+    if !Rect::new(x, y, width, height).contains(Vec2::new(mouse_x, mouse_y)) {
+        return;
+    }
+    
+    let text_size = measure_text(text, None, 16, 1.0);
+    let tip_y = y - 30.0;
+    draw_rectangle(x, tip_y, text_size.width + 12.0, 24.0, Color::new(0.0, 0.0, 0.0, 0.85));
+    draw_rectangle_lines(x, tip_y, text_size.width + 12.0, 24.0, 1.0, GRAY);
+    draw_text(text, x + 6.0, tip_y + 17.0, 16.0, WHITE);
+}
+
+// Why the local player can't train any more for now
+fn supply_warning(game_state: &GameState) -> &'static str {
+    let at_maximum = game_state.simulation.players.get(game_state.current_player_id)
+        .is_some_and(|player| player.supply_cap >= game_state.simulation.definitions.max_supply);
+    if at_maximum {
+        "Supply at maximum"
+    } else {
+        "Not enough supply: build a Supply Depot"
+    }
+}
+
+// What a locked option's tooltip says the player still needs
+fn requirements_text(missing: &[BuildingType]) -> String {
+    let names: Vec<&str> = missing.iter().map(building_name).collect();
+    format!("Requires {}", names.join(", "))
+}
+
+fn building_name(building_type: &BuildingType) -> &'static str {
+    match building_type {
+        BuildingType::Barracks => "Barracks",
+        BuildingType::Factory => "Factory",
+        BuildingType::ResourceDepot => "Resource Depot",
+        BuildingType::DefenseTurret => "Defense Turret",
+        BuildingType::ResearchCenter => "Research Center",
+        BuildingType::SupplyDepot => "Supply Depot",
+    }
+}
+
+fn upgrade_name(upgrade: &UpgradeType) -> &'static str {
+    match upgrade {
+        UpgradeType::InfantryWeapons => "Infantry Weapons",
+        UpgradeType::InfantryArmor => "Infantry Armor",
+        UpgradeType::VehiclePlating => "Vehicle Plating",
+        UpgradeType::TankCannons => "Tank Cannons",
+    }
+}
+
 // Helper method for color darkening
 trait ColorExt {
     fn darker(&self, amount: f32) -> Self;
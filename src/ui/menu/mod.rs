@@ -1,8 +1,3 @@
-use macroquad::prelude::*;
-use crate::game::{GameState, GameScreen};
-use crate::resources::ResourceManager;
-use crate::audio::AudioManager;
-
 pub mod system;
 pub mod main_menu;
 pub mod settings;
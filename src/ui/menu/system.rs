@@ -116,6 +116,7 @@ impl MenuSystem {
         }
     }
 
+    #[allow(dead_code)]
     fn draw_main_menu(&self) {
         let screen_width = screen_width();
         let screen_height = screen_height();
@@ -154,6 +155,7 @@ impl MenuSystem {
         draw_text(instructions, (screen_width - inst_width) / 2.0, screen_height * 0.9, 20.0, GRAY);
     }
 
+    #[allow(dead_code)]
     fn draw_settings(&self, game_state: &GameState) {
         let screen_width = screen_width();
         let screen_height = screen_height();
@@ -231,3 +233,9 @@ impl MenuSystem {
         draw_text(instructions, (screen_width - inst_width) / 2.0, screen_height() * 0.9, 20.0, GRAY);
     }
 }
+
+impl Default for MenuSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}